@@ -11,3 +11,14 @@ Feature: Base Tests
         When I run my program
         Then I should see "Checked 1 file" in stdout
 
+
+    Scenario: Sitefix fails when asked to write a baseline without a baseline file
+        Given I have a "public/index.html" file with the body:
+            """
+            <p>Hello!</p>
+            """
+        When I run my program with the flags:
+            | --write-baseline |
+        Then I should see "The write_baseline option needs a baseline file to write to." in stderr
+        Then I should see "Error loading Sitefix config:" in stderr
+        Then I should not see "Running Sitefix" in stdout
@@ -0,0 +1,68 @@
+Feature: Baseline Tests
+    Background:
+        Given I have the environment variables:
+            | SITEFIX_SOURCE   | public                |
+            | SITEFIX_BASELINE | sitefix-baseline.json |
+
+    Scenario: Sitefix can write a baseline of the current issues
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="/missing/">Missing</a>
+            """
+        When I run my program with the flags:
+            | --write-baseline |
+        Then I should see "Wrote 1 issue to the baseline \"sitefix-baseline.json\"" in stdout
+        Then I should see "All ok!" in stdout
+
+    Scenario: Sitefix only fails on issues that aren't in the baseline
+        Given I have a "sitefix-baseline.json" file with the content:
+            """
+            {
+              "issues": [
+                { "file": "index.html", "rule": "dead-link", "url": "/missing/", "count": 1 },
+                { "file": "index.html", "rule": "dead-link", "url": "/gone/", "count": 1 }
+              ]
+            }
+            """
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="/missing/">Missing</a>
+            <a href="/beets/">Beets</a>
+            """
+        When I run my program
+        Then I should see "Ignoring 1 known issue from the baseline" in stdout
+        Then I should see "1 issue in the baseline has been fixed:" in stdout
+        Then I should see "* index.html: dead-link /gone/" in stdout
+        Then I should see "1 issue:" in stderr
+        Then I should see "Dead Link: <a> links to /beets/, but that page does not exist" in stderr
+
+    Scenario: Sitefix matches the baseline however the source is passed in
+        Given I have a "sitefix-baseline.json" file with the content:
+            """
+            {
+              "issues": [
+                { "file": "index.html", "rule": "dead-link", "url": "/missing/", "count": 1 }
+              ]
+            }
+            """
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="/missing/">Missing</a>
+            """
+        When I run my program with the flags:
+            | --source ./public |
+        Then I should see "Ignoring 1 known issue from the baseline" in stdout
+        Then I should see "All ok!" in stdout
+
+    Scenario: Sitefix fails on a missing baseline before fixing anything
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="http://beets.com/">Beets</a>
+            """
+        When I run my program with the flags:
+            | --autofix |
+        Then I should see "Failed to read the baseline \"sitefix-baseline.json\"" in stderr
+        Then I should see "Error loading Sitefix config:" in stderr
+        Then I should not see "Running Sitefix" in stdout
+        Then I should see a selector 'a' in "public/index.html" with the attributes:
+            | href | http://beets.com/ |
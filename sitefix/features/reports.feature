@@ -0,0 +1,137 @@
+Feature: Report Tests
+    Background:
+        Given I have the environment variables:
+            | SITEFIX_SOURCE | public |
+
+    Scenario: Sitefix can print a JSON report
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="/missing/">Missing</a>
+            """
+        When I run my program with the flags:
+            | --format json |
+        Then I should see "\"kind\": \"dead-link\"" in stdout
+        Then I should see "\"url\": \"/missing/\"" in stdout
+        Then I should see "Dead Link: <a> links to /missing/, but that page does not exist" in stderr
+
+    Scenario: Sitefix can write a report to a file
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="/missing/">Missing</a>
+            """
+        When I run my program with the flags:
+            | --format json                     |
+            | --report-file sitefix-report.json |
+        Then I should see "Wrote a report to \"sitefix-report.json\"" in stdout
+
+    Scenario: Sitefix can print a SARIF report
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="/missing/">Missing</a>
+            """
+        When I run my program with the flags:
+            | --format sarif |
+        Then I should see "\"version\": \"2.1.0\"" in stdout
+        Then I should see "\"ruleId\": \"dead-link\"" in stdout
+
+    Scenario: Sitefix can print a JUnit report
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="/missing/">Missing</a>
+            """
+        When I run my program with the flags:
+            | --format junit |
+        Then I should see "<testsuite name=\"public/index.html\" tests=\"1\" failures=\"1\" errors=\"0\">" in stdout
+
+    Scenario: Sitefix can print GitHub Actions annotations
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="/missing/">Missing</a>
+            """
+        When I run my program with the flags:
+            | --format github |
+        Then I should see "::error file=public/index.html,line=" in stdout
+        Then I should see ",title=Sitefix dead-link::Dead Link: <a> links to /missing/, but that page does not exist" in stdout
+
+    Scenario: Sitefix prints GitHub Actions annotations when running in GitHub Actions
+        Given I have the environment variables:
+            | GITHUB_ACTIONS | true |
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="/missing/">Missing</a>
+            """
+        When I run my program
+        Then I should see "::error file=public/index.html" in stdout
+
+    Scenario: Sitefix reports where each issue is in the file
+        Given I have a "public/index.html" file with the content:
+            """
+            <html>
+            <body>
+                <p>Beets</p><a href="/missing/">Missing</a>
+            </body>
+            </html>
+            """
+        When I run my program
+        Then I should see "* public/index.html:3:17: Dead Link: <a> links to /missing/, but that page does not exist" in stderr
+
+    Scenario: Sitefix includes positions in GitHub Actions annotations
+        Given I have a "public/index.html" file with the content:
+            """
+            <html>
+            <body>
+                <p>Beets</p><a href="/missing/">Missing</a>
+            </body>
+            </html>
+            """
+        When I run my program with the flags:
+            | --format github |
+        Then I should see "::error file=public/index.html,line=3,col=17,title=Sitefix dead-link::" in stdout
+
+    Scenario: Sitefix reports the source file named on the page
+        Given I have a "public/index.html" file with the body:
+            """
+            <main data-sitefix-source="content/beets.md:12">
+                <p><a href="/missing/">Missing</a></p>
+            </main>
+            """
+        When I run my program
+        Then I should see "(from content/beets.md:12): Dead Link: <a> links to /missing/, but that page does not exist" in stderr
+
+    Scenario: Sitefix reports source files from a source map
+        Given I have a "public/beets/index.html" file with the body:
+            """
+            <a href="/missing/">Missing</a>
+            """
+        Given I have a "source-map.json" file with the content:
+            """
+            { "beets/index.html": "content/beets.md" }
+            """
+        When I run my program with the flags:
+            | --source-map source-map.json |
+        Then I should see "(from content/beets.md): Dead Link: <a> links to /missing/, but that page does not exist" in stderr
+
+    Scenario: Sitefix fails on a missing source map before fixing anything
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="http://beets.com/">Beets</a>
+            """
+        When I run my program with the flags:
+            | --source-map source-map.json |
+            | --autofix                    |
+        Then I should see "Failed to read the source map \"source-map.json\"" in stderr
+        Then I should see "Error loading Sitefix config:" in stderr
+        Then I should not see "Running Sitefix" in stdout
+        Then I should see a selector 'a' in "public/index.html" with the attributes:
+            | href | http://beets.com/ |
+
+    Scenario: Sitefix fails on an unknown report format
+        Given I have a "public/index.html" file with the body:
+            """
+            <p>Hello!</p>
+            """
+        When I run my program with the flags:
+            | --format xml |
+        Then I should see "Unknown format \"xml\"" in stderr
+        Then I should see "Error loading Sitefix config:" in stderr
+        Then I should not see "Running Sitefix" in stdout
@@ -0,0 +1,69 @@
+Feature: Rule Tests
+    Background:
+        Given I have the environment variables:
+            | SITEFIX_SOURCE | public |
+
+    Scenario: Sitefix can downgrade a rule to a warning
+        Given I have a "sitefix.toml" file with the content:
+            """
+            [rules]
+            dead-link = "warn"
+            """
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="/missing/">Missing</a>
+            """
+        When I run my program
+        Then I should see "1 warning" in stdout
+        Then I should see "Dead Link: <a> links to /missing/, but that page does not exist" in stdout
+        Then I should see "All ok!" in stdout
+
+    Scenario: Sitefix can turn a rule off
+        Given I have a "sitefix.yml" file with the content:
+            """
+            rules:
+              insecure-link: "off"
+            """
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="http://beets.com/">Beets</a>
+            """
+        When I run my program
+        Then I should see "All ok!" in stdout
+
+    Scenario: Sitefix can upgrade a warning to an error
+        Given I have a "public/beets/index.html" file with the body:
+            """
+            <p>Beets!</p>
+            """
+        Given I have a "public/index.html" file with the body:
+            """
+            <a href="/beets">Beets</a>
+            """
+        When I run my program with the flags:
+            | --rules non-trailing=error |
+        Then I should see "1 issue" in stderr
+        Then I should see "Non-trailing: <a> links to /beets instead of /beets/" in stderr
+
+    Scenario: Sitefix rejects unknown rules
+        Given I have a "public/index.html" file with the body:
+            """
+            <p>Beets!</p>
+            """
+        When I run my program with the flags:
+            | --rules beets=off |
+        Then I should see "Unknown rule \"beets\"" in stderr
+        Then I should see "Error loading Sitefix config:" in stderr
+        Then I should not see "Running Sitefix" in stdout
+
+    Scenario: Sitefix fails on unknown rule levels
+        Given I have the environment variables:
+            | SITEFIX_RULES | dead-link=bogus |
+        Given I have a "public/index.html" file with the body:
+            """
+            <p>Beets!</p>
+            """
+        When I run my program
+        Then I should see "Unknown level \"bogus\" for the dead-link rule" in stderr
+        Then I should see "Error loading Sitefix config:" in stderr
+        Then I should not see "Running Sitefix" in stdout
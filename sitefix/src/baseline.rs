@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use crate::reporters::{display_path, ReportedIssue};
+
+/// Issues that were already known when the baseline was written, which don't fail the run.
+/// Line numbers shift as pages change, so issues are matched on their file, rule and URL,
+/// and counted in case the same link appears more than once. Files are recorded relative
+/// to the source directory, so the baseline holds however the source is passed in.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    issues: Vec<BaselineEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    pub file: String,
+    pub rule: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    pub count: usize,
+}
+
+impl Display for BaselineEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.file, self.rule)?;
+        if let Some(url) = &self.url {
+            write!(f, " {url}")?;
+        }
+        if self.count > 1 {
+            write!(f, " (x{})", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+type BaselineKey = (String, String, Option<String>);
+
+fn key(issue: &ReportedIssue, source: &Path) -> BaselineKey {
+    let file = issue.file.strip_prefix(source).unwrap_or(&issue.file);
+    (
+        display_path(file).replace('\\', "/"),
+        issue.issue.issue.kind().to_string(),
+        issue.issue.url.clone(),
+    )
+}
+
+/// The issues from a run, split by whether they were in the baseline
+#[derive(Debug, Default)]
+pub struct BaselineComparison {
+    pub new: Vec<ReportedIssue>,
+    pub known: Vec<ReportedIssue>,
+    /// Baseline entries that no longer appear on the site
+    pub fixed: Vec<BaselineEntry>,
+}
+
+impl Baseline {
+    /// Records the given issues as known
+    pub fn new<'a>(issues: impl IntoIterator<Item = &'a ReportedIssue>, source: &Path) -> Self {
+        // A BTreeMap keeps the file in a stable order between runs
+        let mut counts: BTreeMap<BaselineKey, usize> = BTreeMap::new();
+        for issue in issues {
+            *counts.entry(key(issue, source)).or_default() += 1;
+        }
+
+        Self {
+            issues: counts
+                .into_iter()
+                .map(|((file, rule, url), count)| BaselineEntry {
+                    file,
+                    rule,
+                    url,
+                    count,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read(path)?;
+        serde_json::from_slice(&contents).map_err(|error| Error::new(ErrorKind::InvalidData, error))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+        std::fs::write(path, contents)
+    }
+
+    /// How many issues are recorded, counting repeats
+    pub fn issue_count(&self) -> usize {
+        self.issues.iter().map(|entry| entry.count).sum()
+    }
+
+    /// Splits the issues from a run into new and known issues,
+    /// and finds the baseline entries that have since been fixed
+    pub fn compare(&self, issues: Vec<ReportedIssue>, source: &Path) -> BaselineComparison {
+        let mut remaining: BTreeMap<BaselineKey, usize> = self
+            .issues
+            .iter()
+            .map(|entry| {
+                let key = (entry.file.clone(), entry.rule.clone(), entry.url.clone());
+                (key, entry.count)
+            })
+            .collect();
+
+        let mut comparison = BaselineComparison::default();
+        for issue in issues {
+            match remaining.get_mut(&key(&issue, source)) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    comparison.known.push(issue);
+                }
+                _ => comparison.new.push(issue),
+            }
+        }
+
+        comparison.fixed = remaining
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|((file, rule, url), count)| BaselineEntry {
+                file,
+                rule,
+                url,
+                count,
+            })
+            .collect();
+        comparison
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporters::Severity;
+    use crate::{PageIssue, Position, SitefixIssue};
+    use std::path::PathBuf;
+
+    fn dead_link(file: &str, url: &str, line: usize) -> ReportedIssue {
+        ReportedIssue {
+            file: PathBuf::from(file),
+            issue: PageIssue::new(
+                SitefixIssue::DeadLink(format!("<a> links to {url}, but that page does not exist")),
+                "a",
+                "href",
+                Some(url),
+                Position {
+                    offset: 0,
+                    line,
+                    column: 1,
+                },
+            ),
+            severity: Severity::Error,
+        }
+    }
+
+    #[test]
+    fn baseline_matches_without_lines() {
+        let source = Path::new("public");
+        let baseline = Baseline::new(
+            &[
+                dead_link("public/index.html", "/beets/", 1),
+                dead_link("public/index.html", "/beets/", 2),
+                dead_link("public/index.html", "/carrots/", 3),
+            ],
+            source,
+        );
+        assert_eq!(baseline.issue_count(), 3);
+
+        let comparison = baseline.compare(
+            vec![
+                dead_link("public/index.html", "/beets/", 10),
+                dead_link("public/index.html", "/kale/", 11),
+                dead_link("public/about/index.html", "/beets/", 1),
+            ],
+            source,
+        );
+
+        assert_eq!(comparison.known.len(), 1);
+        let new: Vec<_> = comparison
+            .new
+            .iter()
+            .map(|issue| issue.issue.url.as_deref().unwrap())
+            .collect();
+        assert_eq!(new, vec!["/kale/", "/beets/"]);
+        let fixed: Vec<_> = comparison.fixed.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            fixed,
+            vec![
+                "index.html: dead-link /beets/",
+                "index.html: dead-link /carrots/"
+            ]
+        );
+    }
+
+    #[test]
+    fn baseline_matches_across_source_paths() {
+        let baseline = Baseline::new(
+            &[dead_link("public/about/index.html", "/beets/", 1)],
+            Path::new("public"),
+        );
+
+        let comparison = baseline.compare(
+            vec![dead_link("./public/about/index.html", "/beets/", 1)],
+            Path::new("./public"),
+        );
+        assert_eq!(comparison.known.len(), 1);
+
+        let comparison = baseline.compare(
+            vec![dead_link("/site/public/about/index.html", "/beets/", 1)],
+            Path::new("/site/public/"),
+        );
+        assert_eq!(comparison.known.len(), 1);
+        assert!(comparison.new.is_empty());
+        assert!(comparison.fixed.is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::LinkStatus;
+
+/// External link results from previous runs, stored on disk
+/// so that repeated runs only request links that have expired.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExternalCache {
+    // A BTreeMap keeps the file in a stable order between runs
+    links: BTreeMap<String, CachedLink>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct CachedLink {
+    status: u16,
+    /// Seconds since the Unix epoch
+    checked_at: u64,
+}
+
+impl ExternalCache {
+    /// Reads the cache file, treating a missing file as an empty cache
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        match std::fs::read(path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|error| Error::new(ErrorKind::InvalidData, error)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+        std::fs::write(path, contents)
+    }
+
+    /// The cached status for a URL, if it was checked within the max age
+    pub fn get(&self, url: &str, max_age: Duration, now: u64) -> Option<LinkStatus> {
+        self.links
+            .get(url)
+            .filter(|link| now.saturating_sub(link.checked_at) < max_age.as_secs())
+            .map(|link| LinkStatus::from(Ok(link.status)))
+    }
+
+    /// Stores the status for a URL. Results that are likely to be temporary,
+    /// such as timeouts and server errors, are never cached.
+    pub fn record(&mut self, url: &str, status: &LinkStatus, now: u64) {
+        let status = match status {
+            LinkStatus::Ok(status) => *status,
+            LinkStatus::Failed(status) if *status < 500 && *status != 429 => *status,
+            _ => {
+                self.links.remove(url);
+                return;
+            }
+        };
+
+        self.links.insert(
+            url.to_string(),
+            CachedLink {
+                status,
+                checked_at: now,
+            },
+        );
+    }
+
+    /// Drops any results older than the max age, so the file doesn't grow forever
+    pub fn prune(&mut self, max_age: Duration, now: u64) {
+        self.links
+            .retain(|_, link| now.saturating_sub(link.checked_at) < max_age.as_secs());
+    }
+}
+
+/// The current time, as stored in the cache
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external::RequestError;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    #[test]
+    fn cached_results_expire() {
+        let mut cache = ExternalCache::default();
+        cache.record("https://a.com/", &LinkStatus::Ok(200), 1000);
+        cache.record("https://b.com/", &LinkStatus::Failed(404), 1000);
+
+        assert_eq!(
+            cache.get("https://a.com/", HOUR, 1000 + 60),
+            Some(LinkStatus::Ok(200))
+        );
+        assert_eq!(
+            cache.get("https://b.com/", HOUR, 1000 + 60),
+            Some(LinkStatus::Failed(404))
+        );
+        assert_eq!(cache.get("https://a.com/", HOUR, 1000 + 3600), None);
+        assert_eq!(cache.get("https://c.com/", HOUR, 1000), None);
+
+        cache.prune(HOUR, 1000 + 3600);
+        assert!(cache.links.is_empty());
+    }
+
+    #[test]
+    fn temporary_failures_are_not_cached() {
+        let mut cache = ExternalCache::default();
+        cache.record("https://a.com/", &LinkStatus::Ok(200), 1000);
+        cache.record("https://a.com/", &LinkStatus::Failed(503), 2000);
+        cache.record("https://b.com/", &LinkStatus::Failed(429), 2000);
+        cache.record(
+            "https://c.com/",
+            &LinkStatus::Unreachable(RequestError::Timeout),
+            2000,
+        );
+
+        assert!(cache.links.is_empty());
+    }
+
+    #[test]
+    fn cache_round_trips() {
+        let path = std::env::temp_dir().join(format!("sitefix-cache-{}.json", std::process::id()));
+        let mut cache = ExternalCache::default();
+        cache.record("https://a.com/", &LinkStatus::Ok(200), 1000);
+        cache.save(&path).unwrap();
+
+        let loaded = ExternalCache::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.links, cache.links);
+
+        let missing = ExternalCache::load(&path).unwrap();
+        assert!(missing.links.is_empty());
+    }
+}
@@ -0,0 +1,566 @@
+use async_trait::async_trait;
+use futures::future::join_all;
+use hashbrown::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::time::{sleep, sleep_until, Duration, Instant};
+
+use crate::fossick::urls::split_host;
+use crate::{FixOptions, PageIssue, Position, SitefixIssue, SourceLocation};
+
+pub use cache::{now, ExternalCache};
+
+mod cache;
+
+/// The HTTP method used for a request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    Head,
+    Get,
+}
+
+/// Why a request failed to return a response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RequestError {
+    /// The domain could not be resolved
+    Dns,
+    /// No response arrived before the timeout
+    Timeout,
+    /// Anything else that stopped the request, such as a refused connection
+    Connection(String),
+}
+
+/// Makes the requests for external link checks, and returns the status code of the response.
+/// This sits behind a trait so that tests can run against a mock server or client.
+#[async_trait]
+pub trait HttpClient: Send + Sync {
+    async fn request(
+        &self,
+        method: Method,
+        url: &str,
+        timeout: Duration,
+    ) -> Result<u16, RequestError>;
+}
+
+/// The HTTP client used outside of tests
+pub struct ReqwestClient {
+    client: reqwest::Client,
+}
+
+impl ReqwestClient {
+    pub fn new(version: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .user_agent(format!("sitefix/{version}"))
+            .build()
+            .expect("HTTP client should build");
+
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl HttpClient for ReqwestClient {
+    async fn request(
+        &self,
+        method: Method,
+        url: &str,
+        timeout: Duration,
+    ) -> Result<u16, RequestError> {
+        let method = match method {
+            Method::Head => reqwest::Method::HEAD,
+            Method::Get => reqwest::Method::GET,
+        };
+
+        match self
+            .client
+            .request(method, url)
+            .timeout(timeout)
+            .send()
+            .await
+        {
+            Ok(response) => Ok(response.status().as_u16()),
+            Err(error) if error.is_timeout() => Err(RequestError::Timeout),
+            Err(error) => Err(classify_error(&error)),
+        }
+    }
+}
+
+// reqwest doesn't expose DNS failures directly, so we look for hyper's
+// resolver error in the chain, and otherwise report the deepest cause.
+fn classify_error(error: &reqwest::Error) -> RequestError {
+    let mut cause: &dyn Error = error;
+    loop {
+        if cause.to_string().starts_with("dns error") {
+            return RequestError::Dns;
+        }
+        match cause.source() {
+            Some(source) => cause = source,
+            None => return RequestError::Connection(cause.to_string()),
+        }
+    }
+}
+
+/// A link to another site, found on a page
+#[derive(Clone, Debug)]
+pub struct ExternalLink {
+    pub element: String,
+    pub attribute: String,
+    /// How the linking attribute is referred to in issue messages
+    pub label: String,
+    /// The link as written on the page
+    pub href: String,
+    /// The URL to request, without any hash segment
+    pub url: String,
+    pub position: Position,
+    pub source: Option<SourceLocation>,
+}
+
+impl ExternalLink {
+    pub fn new(
+        element: &str,
+        attribute: &str,
+        label: String,
+        href: String,
+        url: &str,
+        position: Position,
+    ) -> Self {
+        let url = url.split_once('#').map_or(url, |(url, _)| url);
+        // Protocol-relative links are checked over https
+        let url = match url.strip_prefix("//") {
+            Some(rest) => format!("https://{rest}"),
+            None => url.to_string(),
+        };
+
+        Self {
+            element: element.into(),
+            attribute: attribute.into(),
+            label,
+            href,
+            url,
+            position,
+            source: None,
+        }
+    }
+}
+
+/// The outcome of checking an external URL
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkStatus {
+    Ok(u16),
+    Failed(u16),
+    Unreachable(RequestError),
+}
+
+impl From<Result<u16, RequestError>> for LinkStatus {
+    fn from(result: Result<u16, RequestError>) -> Self {
+        match result {
+            Ok(status) if status < 400 => LinkStatus::Ok(status),
+            Ok(status) => LinkStatus::Failed(status),
+            Err(error) => LinkStatus::Unreachable(error),
+        }
+    }
+}
+
+impl LinkStatus {
+    /// The issue to raise for a link with this status, if any, and whether it is only a warning.
+    /// Failures that are likely to be temporary are warned about rather than failing the run.
+    pub fn issue(&self, link: &ExternalLink) -> Option<(PageIssue, bool)> {
+        let ExternalLink { label, href, .. } = link;
+        let (message, warning) = match self {
+            LinkStatus::Ok(_) => return None,
+            LinkStatus::Failed(429) => (
+                format!("{label} links to {href}, but the site is rate limiting requests"),
+                true,
+            ),
+            LinkStatus::Failed(status) => (
+                format!(
+                    "{label} links to {href}, which returned {}",
+                    describe_status(*status)
+                ),
+                false,
+            ),
+            LinkStatus::Unreachable(RequestError::Dns) => (
+                format!("{label} links to {href}, but its domain could not be found"),
+                false,
+            ),
+            LinkStatus::Unreachable(RequestError::Timeout) => {
+                (format!("{label} links to {href}, which timed out"), true)
+            }
+            LinkStatus::Unreachable(RequestError::Connection(reason)) => (
+                format!("{label} links to {href}, which could not be reached: {reason}"),
+                true,
+            ),
+        };
+
+        let mut issue = PageIssue::new(
+            SitefixIssue::ExternalLink(message),
+            &link.element,
+            &link.attribute,
+            Some(href),
+            link.position,
+        );
+        issue.source = link.source.clone();
+        Some((issue, warning))
+    }
+}
+
+fn describe_status(status: u16) -> String {
+    match reqwest::StatusCode::from_u16(status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+    {
+        Some(reason) => format!("{status} {reason}"),
+        None => status.to_string(),
+    }
+}
+
+/// Checks external URLs, limiting how many requests are in flight
+/// and spacing out requests to the same host.
+pub struct ExternalChecker {
+    client: Arc<dyn HttpClient>,
+    concurrency: usize,
+    timeout: Duration,
+    retries: u32,
+    host_delay: Duration,
+    retry_delay: Duration,
+}
+
+impl ExternalChecker {
+    pub fn new(client: Arc<dyn HttpClient>, options: &FixOptions) -> Self {
+        Self {
+            client,
+            concurrency: options.external_concurrency,
+            timeout: options.external_timeout,
+            retries: options.external_retries,
+            host_delay: options.external_host_delay,
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Checks each distinct URL once, returning the status of each
+    pub async fn check(&self, urls: HashSet<String>) -> HashMap<String, LinkStatus> {
+        let permits = Semaphore::new(self.concurrency);
+        let hosts: HashMap<String, Mutex<Option<Instant>>> = urls
+            .iter()
+            .map(|url| (host(url), Mutex::new(None)))
+            .collect();
+
+        let checks = urls.into_iter().map(|url| {
+            let (permits, hosts) = (&permits, &hosts);
+            async move {
+                let status = self.check_url(&url, permits, &hosts[&host(&url)]).await;
+                (url, status)
+            }
+        });
+
+        join_all(checks).await.into_iter().collect()
+    }
+
+    async fn check_url(
+        &self,
+        url: &str,
+        permits: &Semaphore,
+        host: &Mutex<Option<Instant>>,
+    ) -> LinkStatus {
+        let mut attempt = 0;
+        loop {
+            // Some servers don't support HEAD requests, so we retry any failure as a GET
+            let mut result = self.request(Method::Head, url, permits, host).await;
+            if matches!(result, Ok(status) if status >= 400) {
+                result = self.request(Method::Get, url, permits, host).await;
+            }
+
+            let retryable = match result {
+                Ok(status) => status == 429 || status >= 500,
+                Err(RequestError::Dns) => false,
+                Err(_) => true,
+            };
+            if !retryable || attempt >= self.retries {
+                return result.into();
+            }
+
+            attempt += 1;
+            sleep(self.retry_delay * attempt).await;
+        }
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        url: &str,
+        permits: &Semaphore,
+        host: &Mutex<Option<Instant>>,
+    ) -> Result<u16, RequestError> {
+        let _permit = {
+            // Holding the lock while we wait queues up other requests to this host behind us
+            let mut next_request = host.lock().await;
+            if let Some(next_request) = *next_request {
+                sleep_until(next_request).await;
+            }
+            let permit = permits.acquire().await.expect("Semaphore is never closed");
+            *next_request = Some(Instant::now() + self.host_delay);
+            permit
+        };
+
+        self.client.request(method, url, self.timeout).await
+    }
+}
+
+fn host(url: &str) -> String {
+    split_host(url)
+        .map(|(host, _)| host.to_lowercase())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const POSITION: Position = Position {
+        offset: 0,
+        line: 1,
+        column: 1,
+    };
+
+    // Responds to each URL with a fixed sequence of results, repeating the last one
+    struct MockClient {
+        responses: HashMap<String, Vec<Result<u16, RequestError>>>,
+        requests: std::sync::Mutex<Vec<(Method, String, Instant)>>,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    impl MockClient {
+        fn new(responses: Vec<(&str, Vec<Result<u16, RequestError>>)>) -> Self {
+            Self {
+                responses: responses
+                    .into_iter()
+                    .map(|(url, results)| (url.to_string(), results))
+                    .collect(),
+                requests: Default::default(),
+                in_flight: AtomicUsize::new(0),
+                max_in_flight: AtomicUsize::new(0),
+            }
+        }
+
+        fn requests(&self) -> Vec<(Method, String, Instant)> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl HttpClient for MockClient {
+        async fn request(
+            &self,
+            method: Method,
+            url: &str,
+            _timeout: Duration,
+        ) -> Result<u16, RequestError> {
+            let attempt = {
+                let mut requests = self.requests.lock().unwrap();
+                requests.push((method, url.to_string(), Instant::now()));
+                requests.iter().filter(|(_, u, _)| u == url).count() - 1
+            };
+
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            sleep(Duration::from_millis(10)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            let results = &self.responses[url];
+            results[attempt.min(results.len() - 1)].clone()
+        }
+    }
+
+    fn checker(client: &Arc<MockClient>) -> ExternalChecker {
+        ExternalChecker {
+            client: Arc::clone(client) as Arc<dyn HttpClient>,
+            concurrency: 2,
+            timeout: Duration::from_secs(10),
+            retries: 2,
+            host_delay: Duration::from_millis(100),
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+
+    fn urls(urls: &[&str]) -> HashSet<String> {
+        urls.iter().map(|url| url.to_string()).collect()
+    }
+
+    #[test]
+    fn external_link_urls() {
+        let link = ExternalLink::new(
+            "a",
+            "href",
+            "<a>".into(),
+            "".into(),
+            "https://example.com/beets/#top",
+            POSITION,
+        );
+        assert_eq!(link.url, "https://example.com/beets/");
+        let link = ExternalLink::new(
+            "a",
+            "href",
+            "<a>".into(),
+            "".into(),
+            "//example.com/beets/",
+            POSITION,
+        );
+        assert_eq!(link.url, "https://example.com/beets/");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_statuses() {
+        let client = Arc::new(MockClient::new(vec![
+            ("https://a.com/", vec![Ok(200)]),
+            ("https://b.com/", vec![Ok(404)]),
+            ("https://c.com/", vec![Err(RequestError::Dns)]),
+        ]));
+        let statuses = checker(&client)
+            .check(urls(&[
+                "https://a.com/",
+                "https://b.com/",
+                "https://c.com/",
+            ]))
+            .await;
+
+        assert_eq!(statuses["https://a.com/"], LinkStatus::Ok(200));
+        assert_eq!(statuses["https://b.com/"], LinkStatus::Failed(404));
+        assert_eq!(
+            statuses["https://c.com/"],
+            LinkStatus::Unreachable(RequestError::Dns)
+        );
+        // The 404 is retried as a GET, but the DNS failure isn't retried at all
+        assert_eq!(client.requests().len(), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn falls_back_to_get() {
+        let client = Arc::new(MockClient::new(vec![(
+            "https://a.com/",
+            vec![Ok(405), Ok(200)],
+        )]));
+        let statuses = checker(&client).check(urls(&["https://a.com/"])).await;
+
+        assert_eq!(statuses["https://a.com/"], LinkStatus::Ok(200));
+        let methods: Vec<_> = client.requests().into_iter().map(|r| r.0).collect();
+        assert_eq!(methods, vec![Method::Head, Method::Get]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_transient_failures() {
+        let client = Arc::new(MockClient::new(vec![
+            ("https://a.com/", vec![Err(RequestError::Timeout), Ok(200)]),
+            ("https://b.com/", vec![Ok(503)]),
+        ]));
+        let statuses = checker(&client)
+            .check(urls(&["https://a.com/", "https://b.com/"]))
+            .await;
+
+        assert_eq!(statuses["https://a.com/"], LinkStatus::Ok(200));
+        assert_eq!(statuses["https://b.com/"], LinkStatus::Failed(503));
+        let b_requests = client
+            .requests()
+            .into_iter()
+            .filter(|r| r.1 == "https://b.com/")
+            .count();
+        // A HEAD and a GET for the first attempt and each of the two retries
+        assert_eq!(b_requests, 6);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_concurrency_and_host_rate() {
+        let client = Arc::new(MockClient::new(vec![
+            ("https://a.com/1", vec![Ok(200)]),
+            ("https://a.com/2", vec![Ok(200)]),
+            ("https://a.com/3", vec![Ok(200)]),
+            ("https://b.com/", vec![Ok(200)]),
+            ("https://c.com/", vec![Ok(200)]),
+            ("https://d.com/", vec![Ok(200)]),
+        ]));
+        checker(&client)
+            .check(urls(&[
+                "https://a.com/1",
+                "https://a.com/2",
+                "https://a.com/3",
+                "https://b.com/",
+                "https://c.com/",
+                "https://d.com/",
+            ]))
+            .await;
+
+        assert_eq!(client.max_in_flight.load(Ordering::SeqCst), 2);
+
+        let mut a_times: Vec<_> = client
+            .requests()
+            .into_iter()
+            .filter(|r| r.1.starts_with("https://a.com/"))
+            .map(|r| r.2)
+            .collect();
+        a_times.sort();
+        for pair in a_times.windows(2) {
+            assert!(pair[1] - pair[0] >= Duration::from_millis(100));
+        }
+    }
+
+    // Serves each request with the status code given in its path, e.g. /404
+    async fn mock_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buf = [0; 1024];
+                    let read = socket.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..read]);
+                    let status = request
+                        .split_whitespace()
+                        .nth(1)
+                        .and_then(|path| path.trim_start_matches('/').parse::<u16>().ok())
+                        .unwrap_or(200);
+                    let response = format!(
+                        "HTTP/1.1 {status} Mock\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        format!("http://{address}")
+    }
+
+    #[tokio::test]
+    async fn reqwest_client() {
+        let server = mock_server().await;
+        let client = ReqwestClient::new("test");
+        let timeout = Duration::from_secs(5);
+
+        assert_eq!(
+            client
+                .request(Method::Head, &format!("{server}/200"), timeout)
+                .await,
+            Ok(200)
+        );
+        assert_eq!(
+            client
+                .request(Method::Get, &format!("{server}/404"), timeout)
+                .await,
+            Ok(404)
+        );
+
+        // Nothing is listening once the listener is dropped
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = closed.local_addr().unwrap();
+        drop(closed);
+        assert!(matches!(
+            client
+                .request(Method::Head, &format!("http://{address}/"), timeout)
+                .await,
+            Err(RequestError::Connection(_))
+        ));
+    }
+}
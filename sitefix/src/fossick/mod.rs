@@ -1,20 +1,34 @@
-use std::io::Error;
-use std::path::PathBuf;
+use hashbrown::HashSet;
+use lol_html::errors::RewritingError;
+use similar::TextDiff;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, BufReader};
 use tokio::time::{sleep, Duration};
 
-use crate::{FixOptions, Globals, SitefixIssue};
-use parser::DomParser;
+use crate::external::ExternalLink;
+use crate::{FixOptions, Globals, PageIssue};
+use parser::{DomParser, IdParser};
 
 use self::parser::DomParserResult;
 
 mod parser;
+mod positions;
+pub mod urls;
 
 #[derive(Debug)]
 pub struct FossickedData {
     pub file_path: PathBuf,
-    pub issues: Vec<SitefixIssue>,
+    pub issues: Vec<PageIssue>,
+    pub warnings: Vec<PageIssue>,
+    pub fixes: Vec<PageIssue>,
+    /// Links to other sites, to be checked with --check-external
+    pub external_links: Vec<ExternalLink>,
+    /// A unified diff of the fixes, when running in dry-run mode
+    pub diff: Option<String>,
+    /// Whether the file was changed, by fixes or by stripping source attributes
+    pub rewritten: bool,
     pub has_html_element: bool,
 }
 
@@ -22,6 +36,7 @@ pub struct FossickedData {
 pub struct Fossicker {
     pub file_path: PathBuf,
     pub urls: Vec<String>,
+    pub ids: HashSet<String>,
     data: Option<DomParserResult>,
 }
 
@@ -30,14 +45,49 @@ impl Fossicker {
         Self {
             urls: vec![build_url(&file_path, options)],
             file_path,
+            ids: HashSet::new(),
             data: None,
         }
     }
 
+    async fn read_ids(&mut self) -> Result<(), Error> {
+        let file = File::open(&self.file_path).await?;
+
+        let mut parser = IdParser::new();
+
+        let mut br = BufReader::new(file);
+        let mut buf = [0; 20000];
+        while let Ok(read) = br.read(&mut buf).await {
+            if read == 0 {
+                break;
+            }
+            if parser.write(&buf[..read]).is_err() {
+                // The main parse will report this failure,
+                // so we just won't expose any ids for this page.
+                return Ok(());
+            }
+        }
+
+        // As above, a failure here just means this page exposes no ids
+        self.ids = parser.wrap().unwrap_or_default();
+
+        Ok(())
+    }
+
+    /// Records the element ids this page exposes, so that
+    /// links from other pages can be checked against them.
+    pub async fn index(mut self) -> Self {
+        while self.read_ids().await.is_err() {
+            sleep(Duration::from_millis(1)).await;
+        }
+
+        self
+    }
+
     async fn read_file(&mut self, globals: &Globals, options: &FixOptions) -> Result<(), Error> {
         let file = File::open(&self.file_path).await?;
 
-        let mut rewriter = DomParser::new(globals, options);
+        let mut rewriter = DomParser::new(globals, options, self.urls[0].clone());
 
         let mut br = BufReader::new(file);
         let mut buf = [0; 20000];
@@ -46,19 +96,26 @@ impl Fossicker {
                 break;
             }
             if let Err(error) = rewriter.write(&buf[..read]) {
-                println!(
-                    "Failed to parse file {} — skipping this file. Error:\n{error}",
-                    self.file_path.to_str().unwrap_or("[unknown file]")
-                );
+                self.parse_failed(error, options);
                 return Ok(());
             }
         }
 
-        self.data = Some(rewriter.wrap());
+        match rewriter.wrap() {
+            Ok(data) => self.data = Some(data),
+            Err(error) => self.parse_failed(error, options),
+        }
 
         Ok(())
     }
 
+    fn parse_failed(&self, error: RewritingError, options: &FixOptions) {
+        options.logger.error(format!(
+            "Failed to parse file {} — skipping this file. Error:\n{error}",
+            self.file_path.to_str().unwrap_or("[unknown file]")
+        ));
+    }
+
     pub async fn fossick(
         mut self,
         globals: &Globals,
@@ -72,22 +129,98 @@ impl Fossicker {
             return Err(());
         }
 
-        let data = self.data.unwrap();
+        let mut data = self.data.unwrap();
+
+        let mut diff = None;
+        let mut rewritten =
+            data.output.is_some() && (!data.fixes.is_empty() || data.stripped_sources);
+        if let (Some(output), true) = (&data.output, rewritten) {
+            let result = if options.dry_run {
+                tokio::fs::read(&self.file_path)
+                    .await
+                    .map(|original| diff = Some(build_diff(&self.file_path, &original, output)))
+            } else {
+                write_fixed_file(&options.output_path(&self.file_path), output, options).await
+            };
+
+            if let Err(error) = result {
+                options.logger.error(format!(
+                    "Failed to write fixes to {} — Error:\n{error}",
+                    self.file_path.to_str().unwrap_or("[unknown file]")
+                ));
+                rewritten = false;
+                if !options.dry_run {
+                    // The fixes were never made, so they fail the run instead
+                    data.issues.append(&mut data.fixes);
+                }
+            }
+        }
+
         Ok(FossickedData {
             file_path: self.file_path,
             has_html_element: data.has_html_element,
             issues: data.issues,
+            warnings: data.warnings,
+            fixes: data.fixes,
+            external_links: data.external_links,
+            diff,
+            rewritten,
         })
     }
 }
 
-fn build_url(page_url: &PathBuf, options: &FixOptions) -> String {
+// Writes a fixed file, either in place or into the output directory.
+async fn write_fixed_file(path: &Path, contents: &[u8], options: &FixOptions) -> Result<(), Error> {
+    if options.output.is_some() {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // A previous run may have hard linked this file to the source file,
+        // so we need to unlink it rather than writing through to the source.
+        if let Err(error) = tokio::fs::remove_file(path).await {
+            if error.kind() != ErrorKind::NotFound {
+                return Err(error);
+            }
+        }
+    }
+
+    tokio::fs::write(path, contents).await
+}
+
+// Builds a unified diff between the file on disk and the fixed output,
+// with paths prefixed such that it can be applied with `git apply` or `patch -p1`.
+fn build_diff(file_path: &Path, original: &[u8], output: &[u8]) -> String {
+    let path = file_path.to_str().unwrap_or("[unknown file]");
+    let original = String::from_utf8_lossy(original);
+    let output = String::from_utf8_lossy(output);
+
+    TextDiff::from_lines(original.as_ref(), output.as_ref())
+        .unified_diff()
+        .header(&format!("a/{path}"), &format!("b/{path}"))
+        .to_string()
+}
+
+/// Builds the URL that a file in the source directory is served from
+pub fn build_asset_url(file_path: &Path, options: &FixOptions) -> String {
+    let url = file_path
+        .strip_prefix(&options.source)
+        .expect("File was found that does not start with the source directory");
+
+    format!(
+        "{}/{}",
+        options.base_path,
+        url.to_str().unwrap().replace('\\', "/")
+    )
+}
+
+fn build_url(page_url: &Path, options: &FixOptions) -> String {
     let url = page_url
         .strip_prefix(&options.source)
         .expect("File was found that does not start with the source directory");
 
     format!(
-        "/{}",
+        "{}/{}",
+        options.base_path,
         url.to_str().unwrap().to_owned().replace("index.html", "")
     )
 }
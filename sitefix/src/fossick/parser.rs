@@ -1,20 +1,176 @@
+use hashbrown::HashSet;
 use lazy_static::lazy_static;
+use lol_html::errors::RewritingError;
+use lol_html::html_content::Element;
 use lol_html::{element, HtmlRewriter, Settings};
 use regex::Regex;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::default::Default;
 use std::rc::Rc;
-use urlencoding::decode;
+use urlencoding::decode_binary;
 
+use super::positions::PositionTracker;
+use super::urls::{resolve_path, split_host, UrlParts};
+use crate::external::ExternalLink;
+use crate::options::{InternalUrls, RuleLevel};
 use crate::FixOptions;
 use crate::Globals;
-use crate::SitefixIssue;
+use crate::{PageIssue, SitefixIssue, SourceLocation};
 
 lazy_static! {
     static ref EXTERNAL_URL: Regex = Regex::new("^(https?:)?//").unwrap();
 }
 lazy_static! {
-    static ref PAGE_LINK_SELECTORS: Vec<&'static str> = vec!("a");
+    static ref OTHER_SCHEME: Regex = Regex::new("^[a-zA-Z][a-zA-Z0-9+.-]*:").unwrap();
+}
+static ID_SELECTOR: &str = "[id], a[name]";
+// Names the source file that generated an element, e.g. "content/beets.md:12"
+static SOURCE_ATTRIBUTE: &str = "data-sitefix-source";
+static SOURCE_SELECTOR: &str = "[data-sitefix-source]";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LinkKind {
+    // Navigates to another page
+    Page,
+    // Loads a file into the page
+    File,
+    // A srcset-style list of image candidates, each with optional descriptors
+    Srcset,
+}
+
+// How browsers treat a subresource loaded over http on an https page
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MixedContent {
+    // Scripts, stylesheets and frames are blocked outright
+    Active,
+    // Images and media are upgraded to https, or loaded and flagged as insecure
+    Passive,
+}
+
+// An attribute that references another page or file on the site
+struct LinkAttribute {
+    tag: &'static str,
+    attribute: &'static str,
+    kind: LinkKind,
+}
+
+impl LinkAttribute {
+    const fn new(tag: &'static str, attribute: &'static str, kind: LinkKind) -> Self {
+        Self {
+            tag,
+            attribute,
+            kind,
+        }
+    }
+
+    // How this attribute is referred to in issue messages
+    fn label(&self) -> String {
+        match self.tag {
+            "a" => "<a>".into(),
+            tag => format!("<{tag} {}>", self.attribute),
+        }
+    }
+
+    fn target(&self) -> &'static str {
+        match self.kind {
+            LinkKind::Page => "page",
+            LinkKind::File | LinkKind::Srcset => "file",
+        }
+    }
+
+    // Whether this attribute loads a subresource into the page, and how browsers treat it
+    fn mixed_content(&self, el: &Element) -> Option<MixedContent> {
+        match (self.tag, self.attribute) {
+            ("script" | "iframe", _) => Some(MixedContent::Active),
+            ("link", "href") => {
+                let rel = el
+                    .get_attribute("rel")
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                let rel: Vec<_> = rel.split_ascii_whitespace().collect();
+                if rel
+                    .iter()
+                    .any(|r| matches!(*r, "stylesheet" | "preload" | "modulepreload" | "manifest"))
+                {
+                    Some(MixedContent::Active)
+                } else if rel.iter().any(|r| r.ends_with("icon")) {
+                    Some(MixedContent::Passive)
+                } else {
+                    // Canonical, alternate etc. links don't load anything
+                    None
+                }
+            }
+            ("link" | "img" | "source" | "video" | "audio", _) => Some(MixedContent::Passive),
+            _ => None,
+        }
+    }
+
+    // Splits the attribute value into the URLs that need checking
+    fn urls(&self, value: &str) -> Vec<String> {
+        match self.kind {
+            LinkKind::Page | LinkKind::File => vec![value.to_string()],
+            LinkKind::Srcset => parse_srcset(value),
+        }
+    }
+}
+
+static LINK_ATTRIBUTES: &[LinkAttribute] = &[
+    LinkAttribute::new("a", "href", LinkKind::Page),
+    LinkAttribute::new("area", "href", LinkKind::Page),
+    LinkAttribute::new("iframe", "src", LinkKind::Page),
+    LinkAttribute::new("form", "action", LinkKind::Page),
+    LinkAttribute::new("link", "href", LinkKind::File),
+    LinkAttribute::new("link", "imagesrcset", LinkKind::Srcset),
+    LinkAttribute::new("img", "src", LinkKind::File),
+    LinkAttribute::new("img", "srcset", LinkKind::Srcset),
+    LinkAttribute::new("script", "src", LinkKind::File),
+    LinkAttribute::new("source", "src", LinkKind::File),
+    LinkAttribute::new("source", "srcset", LinkKind::Srcset),
+    LinkAttribute::new("video", "src", LinkKind::File),
+    LinkAttribute::new("video", "poster", LinkKind::File),
+    LinkAttribute::new("audio", "src", LinkKind::File),
+];
+
+/// Pulls the candidate URLs out of a srcset attribute, following the
+/// HTML spec's parsing rules: a URL is a run of non-whitespace characters,
+/// and may itself contain commas unless they trail it. Descriptors (such as `2x`
+/// or `400w`) run until the next comma that isn't inside parentheses.
+fn parse_srcset(value: &str) -> Vec<String> {
+    let mut urls = vec![];
+    let mut chars = value.chars().peekable();
+
+    loop {
+        while matches!(chars.peek(), Some(c) if c.is_whitespace() || *c == ',') {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut url = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+            url.push(c);
+        }
+
+        if url.ends_with(',') {
+            // A trailing comma ends the candidate, with no descriptors
+            urls.push(url.trim_end_matches(',').to_string());
+            continue;
+        }
+        urls.push(url);
+
+        let mut depth = 0;
+        for c in chars.by_ref() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                ',' if depth == 0 => break,
+                _ => {}
+            }
+        }
+    }
+
+    urls
 }
 
 // We aren't transforming HTML, just parsing, so we dump the output.
@@ -24,10 +180,27 @@ impl lol_html::OutputSink for EmptySink {
     fn handle_chunk(&mut self, _: &[u8]) {}
 }
 
+// When autofixing, we hold onto the rewritten document so it can be written back to disk.
+// Either way we count what has been written, which tells us where each element starts.
+struct OutputSink {
+    output: Option<Rc<RefCell<Vec<u8>>>>,
+    written: Rc<Cell<usize>>,
+}
+impl lol_html::OutputSink for OutputSink {
+    fn handle_chunk(&mut self, chunk: &[u8]) {
+        self.written.set(self.written.get() + chunk.len());
+        if let Some(output) = &self.output {
+            output.borrow_mut().extend_from_slice(chunk);
+        }
+    }
+}
+
 /// Houses the HTML parsing instance and the internal data while parsing
 pub struct DomParser<'a> {
-    rewriter: HtmlRewriter<'a, EmptySink>,
+    rewriter: HtmlRewriter<'a, OutputSink>,
     data: Rc<RefCell<DomParserData>>,
+    positions: Rc<RefCell<PositionTracker>>,
+    output: Option<Rc<RefCell<Vec<u8>>>>,
 }
 
 // The internal state while parsing,
@@ -37,36 +210,79 @@ pub struct DomParser<'a> {
 struct DomParserData {
     current_node: Rc<RefCell<DomParsingNode>>,
     has_html_element: bool,
+    // Every id (or legacy <a name>) on the page that a hash link can target
+    ids: HashSet<String>,
+    // The first <base> element on the page, if any
+    base: Option<PageBase>,
+    // Whether we've seen any links yet, which a <base> element should precede
+    has_links: bool,
+    // Whether we've removed any source attributes from the output
+    stripped_sources: bool,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+// Where relative links on the page resolve from, as set by a <base> element
+#[derive(Clone, Debug, PartialEq)]
+enum PageBase {
+    Internal(String),
+    // Relative links go to another site, so we can't check them
+    OffSite,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
 enum NodeStatus {
+    #[default]
     Fixing,
     // Our content & children should not be reviewed
     Ignored,
 }
 
-impl Default for NodeStatus {
-    fn default() -> Self {
-        Self::Fixing
-    }
-}
-
 // A single HTML element that we're reading into.
 // Contains a reference to the parent element,
 // and since we collapse this tree upwards while we parse,
 // we don't need to store tree structure.
 #[derive(Default, Debug)]
 struct DomParsingNode {
-    issues: Vec<SitefixIssue>,
+    issues: Vec<PageIssue>,
+    warnings: Vec<PageIssue>,
+    fixes: Vec<PageIssue>,
+    // Same-page hash links can target elements later in the document,
+    // so we hold onto the fragment and the issue to raise until we've seen the whole page.
+    pending_hashes: Vec<(String, PageIssue)>,
+    // Links to other sites, which are checked once every page has been parsed
+    external_links: Vec<ExternalLink>,
     parent: Option<Rc<RefCell<DomParsingNode>>>,
     status: NodeStatus,
+    // Where this element came from, as named by it or its nearest ancestor
+    source: Option<SourceLocation>,
+}
+
+impl DomParsingNode {
+    // Hand our findings up to the parent node, unless we've been ignored.
+    fn collapse_into(&self, parent: &mut DomParsingNode) {
+        match self.status {
+            NodeStatus::Ignored => {}
+            NodeStatus::Fixing => {
+                parent.issues.extend(self.issues.clone());
+                parent.warnings.extend(self.warnings.clone());
+                parent.fixes.extend(self.fixes.clone());
+                parent.pending_hashes.extend(self.pending_hashes.clone());
+                parent.external_links.extend(self.external_links.clone());
+            }
+        };
+    }
 }
 
 /// The fix-relevant data that was retrieved from the given input
 #[derive(Debug)]
 pub struct DomParserResult {
-    pub issues: Vec<SitefixIssue>,
+    pub issues: Vec<PageIssue>,
+    pub warnings: Vec<PageIssue>,
+    pub fixes: Vec<PageIssue>,
+    pub external_links: Vec<ExternalLink>,
+    /// The rewritten document, if we're autofixing
+    pub output: Option<Vec<u8>>,
+    /// Whether source attributes were removed from the output
+    pub stripped_sources: bool,
     pub has_html_element: bool,
 }
 
@@ -82,8 +298,14 @@ macro_rules! enclose {
 }
 
 impl<'a> DomParser<'a> {
-    pub fn new(globals: &'a Globals, options: &'a FixOptions) -> Self {
+    pub fn new(globals: &'a Globals, options: &'a FixOptions, page_url: String) -> Self {
         let data = Rc::new(RefCell::new(DomParserData::default()));
+        let output = options.autofix.then(|| Rc::new(RefCell::new(Vec::new())));
+        let written = Rc::new(Cell::new(0));
+        let positions = Rc::new(RefCell::new(PositionTracker::new(
+            Rc::clone(&written),
+            output.clone(),
+        )));
         let root = format!("{}, {} *", options.root_selector, options.root_selector);
 
         let rewriter = HtmlRewriter::new(
@@ -94,52 +316,175 @@ impl<'a> DomParser<'a> {
                         data.has_html_element = true;
                         Ok(())
                     })},
-                    enclose! { (data) element!(root, move |el| {
+                    enclose! { (data) element!(ID_SELECTOR, move |el| {
+                        collect_id(el, &mut data.borrow_mut().ids);
+                        Ok(())
+                    })},
+                    enclose! { (data, positions, page_url) element!("base[href]", move |el| {
+                        let mut data = data.borrow_mut();
+                        // Only the first <base> element on the page has any effect
+                        if data.base.is_some() {
+                            return Ok(());
+                        }
+
+                        let position = positions.borrow_mut().current();
+                        let source = element_source(el, &data.current_node.borrow());
+                        let href = el.get_attribute("href").unwrap_or_default();
+                        let decoded_href = decode_url(&href);
+                        let mut warnings = vec![];
+                        let site_href = site_relative_url(&decoded_href, options);
+                        if site_href.is_none() && (EXTERNAL_URL.is_match(&decoded_href) || OTHER_SCHEME.is_match(&decoded_href)) {
+                            data.base = Some(PageBase::OffSite);
+                            warnings.push(SitefixIssue::BaseHref(format!("<base> points to {decoded_href}, which is off-site, so relative links on this page can't be checked")));
+                        } else {
+                            let href = site_href.as_deref().unwrap_or(&decoded_href);
+                            let base_url = resolve_path(&page_url, UrlParts::new(href).path);
+                            data.base = Some(PageBase::Internal(base_url));
+                        }
+                        if data.has_links {
+                            warnings.push(SitefixIssue::BaseHref(format!("<base> points to {decoded_href}, but appears after other links on the page. It should be placed before any other links in the <head>")));
+                        }
+
+                        data.current_node.borrow_mut().warnings.extend(warnings.into_iter().map(|warning| {
+                            let mut warning = PageIssue::new(warning, "base", "href", Some(decoded_href.as_ref()), position);
+                            warning.source = source.clone();
+                            warning
+                        }));
+                        Ok(())
+                    })},
+                    enclose! { (data, positions) element!(root, move |el| {
+                        let position = positions.borrow_mut().current();
                         let mut issues = vec![];
-                        let status = if el.has_attribute("data-sitefix-ignore") {
+                        let mut warnings = vec![];
+                        let mut fixes = vec![];
+                        let mut pending_hashes = vec![];
+                        let mut external_links = vec![];
+                        let (parent_status, source) = {
+                            let data = data.borrow();
+                            let parent = data.current_node.borrow();
+                            (parent.status, element_source(el, &parent))
+                        };
+                        let status = if parent_status == NodeStatus::Ignored || el.has_attribute("data-sitefix-ignore") {
                             NodeStatus::Ignored
                         } else {
                             NodeStatus::Fixing
                         };
 
                         let tag_name = el.tag_name();
-                        if PAGE_LINK_SELECTORS.contains(&tag_name.as_str()) {
-                            match el.get_attribute("href") {
-                                Some(url) => {
-                                    let decoded_url = decode(&url).expect("UTF-8");
-                                    if decoded_url.starts_with('#') {
-                                        // TODO: add page-level test category
-                                    } else if EXTERNAL_URL.is_match(&decoded_url) {
-                                        // TODO: add external test category
-                                    } else {
-                                        if let Some((main_url, _hash)) = decoded_url.split_once('#') {
-                                            if !globals.urls.contains(&main_url.to_string()) {
-                                                issues.push(SitefixIssue::DeadLink(format!("<{tag_name}> links to {decoded_url}, but that page does not exist")))
-                                            }
-                                            // TODO: Add site-level hash tester
-                                        } else {
-                                            if !globals.urls.contains(&decoded_url.to_string()) {
-                                                issues.push(SitefixIssue::DeadLink(format!("<{tag_name}> links to {decoded_url}, but that page does not exist")))
+                        // Anchors with a name or id are link targets, rather than links missing an href
+                        let is_anchor = el.has_attribute("name") || el.has_attribute("id");
+                        if tag_name == "a" && !el.has_attribute("href") && !is_anchor {
+                            issues.push(PageIssue::new(
+                                SitefixIssue::MissingLink(format!("<{tag_name}> has no href")),
+                                &tag_name,
+                                "href",
+                                None,
+                                position,
+                            ));
+                        }
+
+                        let base = data.borrow().base.clone();
+                        let resolve_from = match &base {
+                            Some(PageBase::Internal(base_url)) => base_url,
+                            _ => &page_url,
+                        };
+
+                        for link in LINK_ATTRIBUTES.iter().filter(|link| link.tag == tag_name) {
+                            let value = match el.get_attribute(link.attribute) {
+                                Some(value) => value,
+                                None => continue,
+                            };
+                            data.borrow_mut().has_links = true;
+                            let label = link.label();
+                            let mixed_content = link.mixed_content(el);
+                            for url in link.urls(&value).into_iter().filter(|url| !url.is_empty()) {
+                                let decoded_url = decode_url(&url);
+                                let is_site_url = site_relative_url(&decoded_url, options).is_some();
+                                let mut checks = vec![];
+                                // Links to our own site over http are rewritten by the relative link fix instead
+                                if !(is_site_url && options.relative_links) {
+                                    match mixed_content {
+                                        Some(content) if options.secure_site => checks.extend(check_mixed_content(link, content, &decoded_url)),
+                                        Some(_) => {},
+                                        None if link.kind == LinkKind::Page => checks.extend(check_insecure_url(link, &decoded_url, options)),
+                                        None => {},
+                                    }
+                                }
+
+                                let is_external = EXTERNAL_URL.is_match(&decoded_url) && !is_site_url;
+                                if is_external || (OTHER_SCHEME.is_match(&decoded_url) && !is_site_url) {
+                                    // Other sites are checked later with --check-external, and
+                                    // mailto:, tel:, data: and friends aren't files on the site
+                                } else if base == Some(PageBase::OffSite) {
+                                    // Everything else resolves against another site
+                                } else if let (Some(hash), None) = (decoded_url.strip_prefix('#'), &base) {
+                                    pending_hashes.push((
+                                        hash.to_string(),
+                                        PageIssue::new(
+                                            SitefixIssue::DeadHash(format!("{label} links to {decoded_url}, but no such element exists on the page")),
+                                            &tag_name,
+                                            link.attribute,
+                                            Some(&decoded_url),
+                                            position,
+                                        )
+                                    ));
+                                } else {
+                                    checks.extend(check_internal_url(link, &decoded_url, resolve_from, globals, options));
+                                }
+
+                                // We only rewrite attributes that hold a single URL
+                                let can_fix = options.autofix && status == NodeStatus::Fixing && link.kind != LinkKind::Srcset;
+                                let mut fixed_url = url.clone();
+                                let page_issue = |issue| PageIssue::new(issue, &tag_name, link.attribute, Some(&decoded_url), position);
+                                for check in checks {
+                                    match check {
+                                        // Rules that are turned off are neither reported nor fixed
+                                        UrlCheck { ref issue, .. } if options.rule_level(issue.kind()) == Some(RuleLevel::Off) => {},
+                                        UrlCheck { issue, fix: Some(fix), warning } if can_fix => {
+                                            let next_url = fix.apply(&fixed_url);
+                                            fixes.push(page_issue(fix.issue(format!("Rewrote link from {fixed_url} to {next_url}"))));
+                                            fixed_url = next_url;
+                                            // A dry run leaves the file as it is, so the issue still stands
+                                            if options.dry_run {
+                                                if warning { warnings.push(page_issue(issue)) } else { issues.push(page_issue(issue)) }
                                             }
-                                        }
+                                        },
+                                        UrlCheck { issue, warning: false, .. } => issues.push(page_issue(issue)),
+                                        UrlCheck { issue, warning: true, .. } => warnings.push(page_issue(issue)),
                                     }
-                                },
-                                None => issues.push(SitefixIssue::MissingLink(format!("<{tag_name}> has no href"))),
+                                }
+                                if fixed_url != url {
+                                    el.set_attribute(link.attribute, &fixed_url)?;
+                                    positions.borrow_mut().rewrote(&position);
+                                }
+
+                                if is_external && options.check_external {
+                                    let href = decode_url(&fixed_url);
+                                    external_links.push(ExternalLink::new(&tag_name, link.attribute, label.clone(), href, &fixed_url, position));
+                                }
                             }
                         }
 
+                        let found = issues.iter_mut().chain(&mut warnings).chain(&mut fixes);
+                        for issue in found.chain(pending_hashes.iter_mut().map(|(_, issue)| issue)) {
+                            issue.source = source.clone();
+                        }
+                        for link in &mut external_links {
+                            link.source = source.clone();
+                        }
+
                         let node = {
                             let mut data = data.borrow_mut();
-                            let parent_status = data.current_node.borrow().status;
 
                             let node = Rc::new(RefCell::new(DomParsingNode{
                                 parent: Some(Rc::clone(&data.current_node)),
-                                status: match parent_status {
-                                    NodeStatus::Ignored => parent_status,
-                                    _ => status,
-                                },
+                                status,
                                 issues,
-                                ..DomParsingNode::default()
+                                warnings,
+                                fixes,
+                                pending_hashes,
+                                external_links,
+                                source,
                             }));
 
                             data.current_node = Rc::clone(&node);
@@ -162,13 +507,7 @@ impl<'a> DomParser<'a> {
                             }
 
                             let mut parent = data.current_node.borrow_mut();
-
-                            match node.status {
-                                NodeStatus::Ignored => {},
-                                NodeStatus::Fixing => {
-                                    parent.issues.extend(node.issues.clone());
-                                }
-                            };
+                            node.collapse_into(&mut parent);
 
                             Ok(())
                         }});
@@ -188,35 +527,51 @@ impl<'a> DomParser<'a> {
                                 return Ok(());
                             }
 
-
                             let mut parent = data.current_node.borrow_mut();
-
-                            match node.status {
-                                NodeStatus::Ignored => {},
-                                NodeStatus::Fixing => {
-                                    parent.issues.extend(node.issues.clone());
-                                }
-                            };
+                            node.collapse_into(&mut parent);
+                        }
+                        Ok(())
+                    })},
+                    // Registered last, so that the handlers above can read the attribute first
+                    enclose! { (data, positions) element!(SOURCE_SELECTOR, move |el| {
+                        if options.autofix && options.strip_source_attributes {
+                            let position = positions.borrow_mut().current();
+                            el.remove_attribute(SOURCE_ATTRIBUTE);
+                            positions.borrow_mut().rewrote(&position);
+                            data.borrow_mut().stripped_sources = true;
                         }
                         Ok(())
                     })},
                 ],
                 ..Settings::default()
             },
-            EmptySink::default(),
+            OutputSink {
+                output: output.clone(),
+                written,
+            },
         );
 
-        Self { rewriter, data }
+        Self {
+            rewriter,
+            data,
+            positions,
+            output,
+        }
     }
 
     /// Writes a chunk of data to the underlying HTML parser
-    pub fn write(&mut self, data: &[u8]) -> Result<(), lol_html::errors::RewritingError> {
+    pub fn write(&mut self, data: &[u8]) -> Result<(), RewritingError> {
+        self.positions.borrow_mut().read(data);
         self.rewriter.write(data)
     }
 
     /// Performs any post-processing and returns the summated search results
-    pub fn wrap(self) -> DomParserResult {
-        drop(self.rewriter); // Clears the extra Rcs on and within data
+    pub fn wrap(self) -> Result<DomParserResult, RewritingError> {
+        let output = self.output;
+        // Flushes any input that lol_html is still buffering,
+        // and clears the extra Rcs on and within data
+        self.rewriter.end()?;
+        drop(self.positions); // Holds onto the output to measure rewrites
         let data = Rc::try_unwrap(self.data).unwrap().into_inner();
         let mut node = data.current_node;
 
@@ -226,12 +581,7 @@ impl<'a> DomParser<'a> {
             {
                 let node = node.borrow();
                 let mut parent = node.parent.as_ref().unwrap().borrow_mut();
-                match node.status {
-                    NodeStatus::Ignored => {}
-                    NodeStatus::Fixing => {
-                        parent.issues.extend(node.issues.clone());
-                    }
-                };
+                node.collapse_into(&mut parent);
             }
             let old_node = node.borrow();
             let new_node = Rc::clone(old_node.parent.as_ref().unwrap());
@@ -241,34 +591,363 @@ impl<'a> DomParser<'a> {
 
         let node = node.borrow();
 
-        DomParserResult {
-            issues: node.issues.clone(),
+        let mut issues = node.issues.clone();
+        for (hash, issue) in &node.pending_hashes {
+            if !is_valid_fragment(hash, &data.ids) {
+                issues.push(issue.clone());
+            }
+        }
+
+        Ok(DomParserResult {
+            issues,
+            warnings: node.warnings.clone(),
+            fixes: node.fixes.clone(),
+            external_links: node.external_links.clone(),
+            output: output.map(|output| Rc::try_unwrap(output).unwrap().into_inner()),
+            stripped_sources: data.stripped_sources,
             has_html_element: data.has_html_element,
+        })
+    }
+}
+
+// The result of checking a URL that was found on the page
+struct UrlCheck {
+    issue: SitefixIssue,
+    // Warnings are reported, but don't fail the run
+    warning: bool,
+    // How autofix can resolve this issue, if it can
+    fix: Option<UrlFix>,
+}
+
+impl UrlCheck {
+    fn issue(issue: SitefixIssue) -> Self {
+        Self {
+            issue,
+            warning: false,
+            fix: None,
+        }
+    }
+
+    fn warning(issue: SitefixIssue) -> Self {
+        Self {
+            issue,
+            warning: true,
+            fix: None,
+        }
+    }
+
+    fn fixable(self, fix: UrlFix) -> Self {
+        Self {
+            fix: Some(fix),
+            ..self
+        }
+    }
+}
+
+// A change that autofix can make to a URL attribute
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum UrlFix {
+    AddTrailingSlash,
+    RemoveTrailingSlash,
+    // Strips the scheme and host from an absolute URL to our own site
+    MakeRelative,
+    // Swaps an http:// URL for https://, for either a link or a subresource
+    UpgradeScheme(Option<MixedContent>),
+}
+
+impl UrlFix {
+    // Applies this fix to the raw attribute value,
+    // leaving any query string, hash segment, and encoding intact.
+    fn apply(&self, url: &str) -> String {
+        let parts = UrlParts::new(url);
+        match self {
+            UrlFix::AddTrailingSlash => parts.with_path(&format!("{}/", parts.path)),
+            UrlFix::RemoveTrailingSlash => parts.with_path(parts.path.trim_end_matches('/')),
+            UrlFix::MakeRelative => match split_host(url) {
+                Some((_, rest)) if rest.starts_with('/') => rest.to_string(),
+                Some((_, rest)) => format!("/{rest}"),
+                None => url.to_string(),
+            },
+            UrlFix::UpgradeScheme(_) if is_http(url) => format!("https:{}", &url[5..]),
+            UrlFix::UpgradeScheme(_) => url.to_string(),
+        }
+    }
+
+    // The issue to report once this fix has been made
+    fn issue(&self, message: String) -> SitefixIssue {
+        match self {
+            UrlFix::AddTrailingSlash => SitefixIssue::NonTrailing(message),
+            UrlFix::RemoveTrailingSlash => SitefixIssue::Trailing(message),
+            UrlFix::MakeRelative => SitefixIssue::AbsoluteLink(message),
+            UrlFix::UpgradeScheme(None) => SitefixIssue::InsecureLink(message),
+            UrlFix::UpgradeScheme(Some(MixedContent::Active)) => {
+                SitefixIssue::ActiveMixedContent(message)
+            }
+            UrlFix::UpgradeScheme(Some(MixedContent::Passive)) => {
+                SitefixIssue::PassiveMixedContent(message)
+            }
+        }
+    }
+}
+
+fn is_http(url: &str) -> bool {
+    url.get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("http:"))
+}
+
+// Flags links that use http, unless their host is known not to support https.
+fn check_insecure_url(link: &LinkAttribute, url: &str, options: &FixOptions) -> Option<UrlCheck> {
+    if !is_http(url) {
+        return None;
+    }
+    let (host, _) = split_host(url)?;
+    let hostname = host.rsplit_once(':').map_or(host, |(hostname, _)| hostname);
+    if options
+        .insecure_hosts
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(hostname))
+    {
+        return None;
+    }
+
+    let label = link.label();
+    Some(
+        UrlCheck::issue(SitefixIssue::InsecureLink(format!(
+            "{label} links to {url} using http instead of https"
+        )))
+        .fixable(UrlFix::UpgradeScheme(None)),
+    )
+}
+
+// Flags subresources loaded over http. Active mixed content is blocked by browsers,
+// so is an issue, while passive mixed content is only warned about.
+fn check_mixed_content(link: &LinkAttribute, content: MixedContent, url: &str) -> Option<UrlCheck> {
+    if !is_http(url) {
+        return None;
+    }
+
+    let label = link.label();
+    let check = match content {
+        MixedContent::Active => UrlCheck::issue(SitefixIssue::ActiveMixedContent(format!(
+            "{label} loads {url} over http, which browsers will block on an https page"
+        ))),
+        MixedContent::Passive => UrlCheck::warning(SitefixIssue::PassiveMixedContent(format!(
+            "{label} loads {url} over http, which browsers will flag as insecure on an https page"
+        ))),
+    };
+    Some(check.fixable(UrlFix::UpgradeScheme(Some(content))))
+}
+
+// Checks a link to another page on this site, including any hash segment,
+// and enforces the configured trailing slash and relative link styles.
+fn check_internal_url(
+    link: &LinkAttribute,
+    url: &str,
+    page_url: &str,
+    globals: &Globals,
+    options: &FixOptions,
+) -> Vec<UrlCheck> {
+    let label = link.label();
+    let site_url = site_relative_url(url, options);
+    let parts = UrlParts::new(site_url.as_deref().unwrap_or(url));
+    let path = resolve_path(page_url, parts.path);
+
+    let mut checks = vec![];
+    let page = if globals.urls.contains(&path) {
+        if options.internal_urls == Some(InternalUrls::Nontrailing) && is_trailing(&path) {
+            let fixed = UrlFix::RemoveTrailingSlash.apply(url);
+            checks.push(
+                UrlCheck::issue(SitefixIssue::Trailing(format!(
+                    "{label} links to {url} instead of {fixed}"
+                )))
+                .fixable(UrlFix::RemoveTrailingSlash),
+            );
+        }
+        Some(path)
+    } else if globals.assets.contains(&path) {
+        // Hash segments on other files (e.g. PDF pages) aren't ours to check
+        None
+    } else {
+        let trailing_path = format!("{path}/");
+        if path.ends_with('/') || !globals.urls.contains(&trailing_path) {
+            return vec![UrlCheck::issue(SitefixIssue::DeadLink(format!(
+                "{label} links to {url}, but that {} does not exist",
+                link.target()
+            )))];
+        }
+
+        let fixed = UrlFix::AddTrailingSlash.apply(url);
+        let issue = SitefixIssue::NonTrailing(format!("{label} links to {url} instead of {fixed}"));
+        match options.internal_urls {
+            None => checks.push(UrlCheck::warning(issue).fixable(UrlFix::AddTrailingSlash)),
+            Some(InternalUrls::Trailing) => {
+                checks.push(UrlCheck::issue(issue).fixable(UrlFix::AddTrailingSlash))
+            }
+            Some(InternalUrls::Nontrailing) => {}
+        };
+        Some(trailing_path)
+    };
+
+    if let (Some(page), Some(hash)) = (page, parts.hash) {
+        let found = globals
+            .ids
+            .get(&page)
+            .map(|ids| is_valid_fragment(hash, ids))
+            .unwrap_or(false);
+        if !found {
+            return vec![UrlCheck::issue(SitefixIssue::DeadHash(format!(
+                "{label} links to {url}, but no such element exists on that page"
+            )))];
+        }
+    }
+
+    if let Some(site_url) = site_url.filter(|_| options.relative_links) {
+        checks.insert(
+            0,
+            UrlCheck::issue(SitefixIssue::AbsoluteLink(format!(
+                "{label} links to {url}, which should be the relative URL {site_url}"
+            )))
+            .fixable(UrlFix::MakeRelative),
+        );
+    }
+
+    checks
+}
+
+// Turns an absolute URL to our own site into a site-relative URL, so that it can
+// be checked like any other internal link. URLs on our hosts that fall outside of
+// the base path belong to something else on that domain, and are left alone.
+fn site_relative_url(url: &str, options: &FixOptions) -> Option<String> {
+    let (host, rest) = split_host(url)?;
+    if !options
+        .site_hosts
+        .iter()
+        .any(|site_host| site_host.eq_ignore_ascii_case(host))
+    {
+        return None;
+    }
+
+    let rest = match rest.strip_prefix('/') {
+        Some(_) => rest.to_string(),
+        None => format!("/{rest}"),
+    };
+    let path = UrlParts::new(&rest).path;
+    let base_path = &options.base_path;
+    if path == base_path || path.starts_with(&format!("{base_path}/")) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+// Percent-decodes a URL for checking. Sites can contain encodings that aren't valid
+// UTF-8, which we still want to check (and report) rather than fail on.
+fn decode_url(url: &str) -> String {
+    String::from_utf8_lossy(&decode_binary(url.as_bytes())).into_owned()
+}
+
+// Whether a URL is a directory-style URL that could be written without its trailing slash
+fn is_trailing(path: &str) -> bool {
+    path.len() > 1 && path.ends_with('/')
+}
+
+/// Collects only the element ids on a page, so that other pages
+/// can check their hash links against it before the main parse.
+pub struct IdParser<'a> {
+    rewriter: HtmlRewriter<'a, EmptySink>,
+    ids: Rc<RefCell<HashSet<String>>>,
+}
+
+impl<'a> IdParser<'a> {
+    pub fn new() -> Self {
+        let ids = Rc::new(RefCell::new(HashSet::new()));
+
+        let rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers: vec![enclose! { (ids) element!(ID_SELECTOR, move |el| {
+                    collect_id(el, &mut ids.borrow_mut());
+                    Ok(())
+                })}],
+                ..Settings::default()
+            },
+            EmptySink,
+        );
+
+        Self { rewriter, ids }
+    }
+
+    /// Writes a chunk of data to the underlying HTML parser
+    pub fn write(&mut self, data: &[u8]) -> Result<(), RewritingError> {
+        self.rewriter.write(data)
+    }
+
+    /// Returns every id found on the page
+    pub fn wrap(self) -> Result<HashSet<String>, RewritingError> {
+        // Flushes any input that lol_html is still buffering, and clears the extra Rcs on ids
+        self.rewriter.end()?;
+        Ok(Rc::try_unwrap(self.ids).unwrap().into_inner())
+    }
+}
+
+// Records the id of an element, or the name of a legacy <a name> anchor.
+fn collect_id(el: &Element, ids: &mut HashSet<String>) {
+    if let Some(id) = el.get_attribute("id") {
+        ids.insert(id);
+    }
+    if el.tag_name() == "a" {
+        if let Some(name) = el.get_attribute("name") {
+            ids.insert(name);
         }
     }
 }
 
+// The source file named by this element, or else by its nearest ancestor
+fn element_source(el: &Element, parent: &DomParsingNode) -> Option<SourceLocation> {
+    el.get_attribute(SOURCE_ATTRIBUTE)
+        .map(|source| SourceLocation::parse(&source))
+        .or_else(|| parent.source.clone())
+}
+
+// An empty fragment and #top scroll to the top of the page,
+// and don't need a matching element to be valid.
+fn is_valid_fragment(hash: &str, ids: &HashSet<String>) -> bool {
+    hash.is_empty() || hash.eq_ignore_ascii_case("top") || ids.contains(hash)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::options::test_options;
+    use crate::Position;
 
     fn test_raw_parse(input: Vec<&'static str>) -> DomParserResult {
-        let config_args = vec![twelf::Layer::Clap(
-            <crate::SitefixInboundConfig as clap::IntoApp>::command().get_matches_from(vec![
-                "sitefix",
-                "--source",
-                "not_important",
-            ]),
-        )];
-        let config =
-            FixOptions::load(crate::SitefixInboundConfig::with_layers(&config_args).unwrap())
-                .unwrap();
-        let g = Globals::default();
-        let mut rewriter = DomParser::new(&g, &config);
+        test_raw_parse_with_globals(input, &Globals::default())
+    }
+
+    fn test_raw_parse_with_globals(input: Vec<&'static str>, g: &Globals) -> DomParserResult {
+        test_raw_parse_with_args(input, g, vec![])
+    }
+
+    fn test_raw_parse_with_args(
+        input: Vec<&'static str>,
+        g: &Globals,
+        args: Vec<&'static str>,
+    ) -> DomParserResult {
+        test_raw_parse_on_page(input, g, args, "/")
+    }
+
+    fn test_raw_parse_on_page(
+        input: Vec<&'static str>,
+        g: &Globals,
+        args: Vec<&'static str>,
+        page_url: &str,
+    ) -> DomParserResult {
+        let config = test_options(&args);
+        let mut rewriter = DomParser::new(g, &config, page_url.into());
         for line in input {
             let _ = rewriter.write(line.as_bytes());
         }
-        rewriter.wrap()
+        rewriter.wrap().unwrap()
     }
 
     fn test_parse(mut input: Vec<&'static str>) -> DomParserResult {
@@ -287,4 +966,717 @@ mod tests {
 
         assert!(data.issues.is_empty());
     }
+
+    #[test]
+    fn valid_hash_links() {
+        let data = test_parse(vec![
+            "<a href='#beets'>Beets</a>",
+            "<a href='#'>Top</a>",
+            "<a href='#top'>Top</a>",
+            "<h2 id='beets'>Beets</h2>",
+            "<a href='#carrots'>Carrots</a>",
+            "<a name='carrots' href='#beets'>Beets</a>",
+        ]);
+
+        assert!(data.issues.is_empty());
+    }
+
+    #[test]
+    fn bare_anchor_targets() {
+        let data = test_parse(vec![
+            "<h2><a name='intro'></a>Intro</h2>",
+            "<h2><a id='outro'></a>Outro</h2>",
+            "<a href='#intro'>Intro</a>",
+            "<a href='#outro'>Outro</a>",
+        ]);
+
+        assert!(data.issues.is_empty());
+    }
+
+    #[test]
+    fn broken_hash_links() {
+        let data = test_parse(vec![
+            "<a href='#beets'>Beets</a>",
+            "<p name='beets'>Not a valid target</p>",
+        ]);
+
+        assert_eq!(data.issues.len(), 1);
+        assert!(matches!(data.issues[0].issue, SitefixIssue::DeadHash(_)));
+    }
+
+    #[test]
+    fn ignored_hash_links() {
+        let data = test_parse(vec![
+            "<div data-sitefix-ignore>",
+            "<a href='#beets'>This should not return an error</a>",
+            "</div>",
+        ]);
+
+        assert!(data.issues.is_empty());
+    }
+
+    #[test]
+    fn cross_page_hash_links() {
+        let data = test_raw_parse_with_globals(
+            vec![
+                "<html><body>",
+                "<a href='/beets/#beets'>Beets</a>",
+                "<a href='/beets/#'>Beets</a>",
+                "<a href='/beets/#not-beets'>Not beets</a>",
+                "<a href='/carrots/#carrots'>Carrots</a>",
+                "</body></html>",
+            ],
+            &beets_globals(),
+        );
+
+        assert_eq!(data.issues.len(), 2);
+        assert!(matches!(data.issues[0].issue, SitefixIssue::DeadHash(_)));
+        assert!(matches!(data.issues[1].issue, SitefixIssue::DeadLink(_)));
+    }
+
+    #[test]
+    fn id_parser() {
+        let mut parser = IdParser::new();
+        let _ = parser.write(b"<html><body><p id='beets'></p><a name='carrots'></a>");
+        let _ = parser.write(b"<p name='kale'></p></body></html>");
+        let ids = parser.wrap().unwrap();
+
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains("beets"));
+        assert!(ids.contains("carrots"));
+    }
+
+    fn beets_globals() -> Globals {
+        let mut g = Globals::default();
+        g.urls.push("/beets/".into());
+        g.assets.insert("/beets/index.html".into());
+        g.assets.insert("/beets/logo.png".into());
+        g.assets.insert("/beets/report.pdf".into());
+        g.ids
+            .insert("/beets/".into(), HashSet::from_iter(["beets".into()]));
+        g
+    }
+
+    #[test]
+    fn non_trailing_links_warn_by_default() {
+        let data = test_raw_parse_with_globals(
+            vec!["<html><body><a href='/beets#beets'>Beets</a></body></html>"],
+            &beets_globals(),
+        );
+
+        assert!(data.issues.is_empty());
+        assert_eq!(data.warnings.len(), 1);
+        assert_eq!(
+            data.warnings[0].to_string(),
+            "Non-trailing: <a> links to /beets#beets instead of /beets/#beets"
+        );
+    }
+
+    #[test]
+    fn enforced_trailing_links() {
+        let data = test_raw_parse_with_args(
+            vec!["<html><body><a href='/beets'>Beets</a><a href='/beets/'>Beets</a></body></html>"],
+            &beets_globals(),
+            vec!["--internal-urls", "trailing"],
+        );
+
+        assert!(data.warnings.is_empty());
+        assert_eq!(data.issues.len(), 1);
+        assert!(matches!(data.issues[0].issue, SitefixIssue::NonTrailing(_)));
+    }
+
+    #[test]
+    fn enforced_nontrailing_links() {
+        let data = test_raw_parse_with_args(
+            vec!["<html><body><a href='/beets'>Beets</a><a href='/beets/'>Beets</a><a href='/'>Home</a></body></html>"],
+            &beets_globals(),
+            vec!["--internal-urls", "nontrailing"],
+        );
+
+        // The link to / is still dead, but is never a trailing slash issue
+        assert!(data.warnings.is_empty());
+        assert_eq!(data.issues.len(), 2);
+        assert_eq!(
+            data.issues[0].to_string(),
+            "Trailing: <a> links to /beets/ instead of /beets"
+        );
+        assert!(matches!(data.issues[1].issue, SitefixIssue::DeadLink(_)));
+    }
+
+    #[test]
+    fn autofix_trailing_links() {
+        let data = test_raw_parse_with_args(
+            vec![
+                "<html><body><a href='/beets#beets'>Beets</a>",
+                "<div data-sitefix-ignore><a href='/beets'>Beets</a></div>",
+                "</body></html>",
+            ],
+            &beets_globals(),
+            vec!["--internal-urls", "trailing", "--autofix"],
+        );
+
+        assert!(data.issues.is_empty());
+        assert_eq!(data.fixes.len(), 1);
+        assert_eq!(
+            data.fixes[0].to_string(),
+            "Non-trailing: Rewrote link from /beets#beets to /beets/#beets"
+        );
+        assert_eq!(
+            String::from_utf8(data.output.unwrap()).unwrap(),
+            "<html><body><a href=\"/beets/#beets\">Beets</a><div data-sitefix-ignore><a href='/beets'>Beets</a></div></body></html>"
+        );
+    }
+
+    #[test]
+    fn dry_run_keeps_fixable_issues() {
+        let data = test_raw_parse_with_args(
+            vec!["<html><body><a href='/beets'>Beets</a></body></html>"],
+            &beets_globals(),
+            vec!["--internal-urls", "trailing", "--dry-run"],
+        );
+
+        assert_eq!(data.fixes.len(), 1);
+        assert_eq!(data.issues.len(), 1);
+        assert_eq!(
+            data.issues[0].to_string(),
+            "Non-trailing: <a> links to /beets instead of /beets/"
+        );
+    }
+
+    #[test]
+    fn autofix_keeps_unfinished_input() {
+        let data = test_raw_parse_with_args(
+            vec![
+                "<html><body><a href='/beets'>Beets</a>",
+                "<script>var x = \"</scr",
+            ],
+            &beets_globals(),
+            vec!["--internal-urls", "trailing", "--autofix"],
+        );
+
+        assert_eq!(data.fixes.len(), 1);
+        assert_eq!(
+            String::from_utf8(data.output.unwrap()).unwrap(),
+            "<html><body><a href=\"/beets/\">Beets</a><script>var x = \"</scr"
+        );
+    }
+
+    #[test]
+    fn resource_links() {
+        let data = test_raw_parse_with_globals(
+            vec![
+                "<html><head><link rel='stylesheet' href='/style.css'></head><body>",
+                "<img src='/logo.png' alt=''>",
+                "<script src='/app.js'></script>",
+                "<form action=''></form>",
+                "<video poster='/poster.jpg'><source src='https://beets.com/video.mp4'></video>",
+                "<iframe src='/beets/'></iframe>",
+                "<img src='data:image/gif;base64,R0lGODlhAQABAAAAACw='>",
+                "<a href='mailto:beets@beets.com'>Email</a>",
+                "</body></html>",
+            ],
+            &beets_globals(),
+        );
+
+        let issues: Vec<_> = data.issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "Dead Link: <link href> links to /style.css, but that file does not exist",
+                "Dead Link: <img src> links to /logo.png, but that file does not exist",
+                "Dead Link: <script src> links to /app.js, but that file does not exist",
+                "Dead Link: <video poster> links to /poster.jpg, but that file does not exist",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_encodings() {
+        let data = test_raw_parse_with_globals(
+            vec![
+                "<html><body>",
+                "<img src='/a%FF.png' alt=''>",
+                "<a href='/beets/%E2%9C%93'>Beets</a>",
+                "</body></html>",
+            ],
+            &beets_globals(),
+        );
+
+        let issues: Vec<_> = data.issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "Dead Link: <img src> links to /a\u{FFFD}.png, but that file does not exist",
+                "Dead Link: <a> links to /beets/\u{2713}, but that page does not exist",
+            ]
+        );
+    }
+
+    #[test]
+    fn asset_links() {
+        let data = test_raw_parse_with_globals(
+            vec![
+                "<html><body>",
+                "<img src='/beets/logo.png' alt=''>",
+                "<a href='/beets/report.pdf#page=2'>Report</a>",
+                "<a href='/beets/index.html'>Beets</a>",
+                "<a href='/beets/missing.pdf'>Missing</a>",
+                "</body></html>",
+            ],
+            &beets_globals(),
+        );
+
+        assert_eq!(data.issues.len(), 1);
+        assert_eq!(
+            data.issues[0].to_string(),
+            "Dead Link: <a> links to /beets/missing.pdf, but that page does not exist"
+        );
+    }
+
+    #[test]
+    fn srcset_parsing() {
+        assert_eq!(
+            parse_srcset("/a.png, /b.png 2x,/c.png 400w"),
+            vec!["/a.png", "/b.png", "/c.png"]
+        );
+        assert_eq!(
+            parse_srcset("  /image,with,commas.png 1x , /b.png, /c.png,"),
+            vec!["/image,with,commas.png", "/b.png", "/c.png"]
+        );
+        assert_eq!(
+            parse_srcset("/a.png (future-descriptor, 2), /b.png"),
+            vec!["/a.png", "/b.png"]
+        );
+        assert_eq!(parse_srcset(" , "), Vec::<String>::new());
+    }
+
+    #[test]
+    fn srcset_links() {
+        let data = test_raw_parse_with_globals(
+            vec![
+                "<html><head><link rel='preload' as='image' imagesrcset='/beets/logo.png 1x, /beets/preload.png 2x'></head><body>",
+                "<img srcset='/beets/logo.png 1x, /beets/logo-2x.png 2x' src='/beets/logo.png' alt=''>",
+                "<picture><source srcset='/beets/logo.png 400w, https://beets.com/logo.png 800w'></picture>",
+                "</body></html>",
+            ],
+            &beets_globals(),
+        );
+
+        let issues: Vec<_> = data.issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "Dead Link: <link imagesrcset> links to /beets/preload.png, but that file does not exist",
+                "Dead Link: <img srcset> links to /beets/logo-2x.png, but that file does not exist",
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_links() {
+        let mut g = beets_globals();
+        g.urls.push("/beets/carrots.html".into());
+        g.urls.push("/".into());
+
+        let data = test_raw_parse_on_page(
+            vec![
+                "<html><body>",
+                "<a href='carrots.html?search=orange'>Carrots</a>",
+                "<a href='./#beets'>Beets</a>",
+                "<a href='../'>Home</a>",
+                "<img src='logo.png' alt=''>",
+                "<a href='kale.html'>Kale</a>",
+                "<a href='..'>Home</a>",
+                "</body></html>",
+            ],
+            &g,
+            vec![],
+            "/beets/",
+        );
+
+        assert_eq!(data.issues.len(), 1);
+        assert_eq!(
+            data.issues[0].to_string(),
+            "Dead Link: <a> links to kale.html, but that page does not exist"
+        );
+        assert!(data.warnings.is_empty());
+    }
+
+    #[test]
+    fn base_href() {
+        let mut g = beets_globals();
+        g.urls.push("/docs/v2/".into());
+        g.urls.push("/docs/v2/carrots/".into());
+
+        let data = test_raw_parse_on_page(
+            vec![
+                "<html><head><base href='/docs/v2/'></head><body>",
+                "<a href='carrots/'>Carrots</a>",
+                "<a href='../../beets/#beets'>Beets</a>",
+                "<a href='#beets'>Beets</a>",
+                "</body></html>",
+            ],
+            &g,
+            vec![],
+            "/kale/",
+        );
+
+        // The hash link resolves against the base URL, not this page
+        assert!(data.warnings.is_empty());
+        assert_eq!(data.issues.len(), 1);
+        assert_eq!(
+            data.issues[0].to_string(),
+            "Dead Link: <a> links to #beets, but no such element exists on that page"
+        );
+    }
+
+    #[test]
+    fn misplaced_base_href() {
+        let data = test_raw_parse_with_globals(
+            vec![
+                "<html><head><link rel='stylesheet' href='/beets/logo.png'><base href='/beets/'><base href='/kale/'></head><body>",
+                "<img src='logo.png' alt=''>",
+                "</body></html>",
+            ],
+            &beets_globals(),
+        );
+
+        assert!(data.issues.is_empty());
+        assert_eq!(data.warnings.len(), 1);
+        assert!(matches!(data.warnings[0].issue, SitefixIssue::BaseHref(_)));
+    }
+
+    #[test]
+    fn off_site_base_href() {
+        let data = test_parse(vec![
+            "<base href='https://beets.com/'>",
+            "<a href='carrots/'>Carrots</a>",
+            "<a href='#kale'>Kale</a>",
+        ]);
+
+        assert!(data.issues.is_empty());
+        assert_eq!(
+            data.warnings[0].to_string(),
+            "Base Href: <base> points to https://beets.com/, which is off-site, so relative links on this page can't be checked"
+        );
+    }
+
+    #[test]
+    fn base_url_links() {
+        let mut g = Globals::default();
+        g.urls.push("/handbook/".into());
+        g.urls.push("/handbook/beets/".into());
+
+        let data = test_raw_parse_on_page(
+            vec![
+                "<html><body>",
+                "<a href='/handbook/beets/'>Beets</a>",
+                "<a href='https://example.com/handbook/beets/'>Beets</a>",
+                "<a href='//EXAMPLE.com/handbook'>Handbook</a>",
+                "<a href='https://example.com/blog/'>Blog</a>",
+                "<a href='https://example.com/handbook/carrots/'>Carrots</a>",
+                "<a href='/beets/'>Beets</a>",
+                "</body></html>",
+            ],
+            &g,
+            vec!["--base-url", "https://example.com/handbook/"],
+            "/handbook/",
+        );
+
+        let issues: Vec<_> = data.issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "Dead Link: <a> links to https://example.com/handbook/carrots/, but that page does not exist",
+                "Dead Link: <a> links to /beets/, but that page does not exist",
+            ]
+        );
+        assert_eq!(
+            data.warnings[0].to_string(),
+            "Non-trailing: <a> links to //EXAMPLE.com/handbook instead of //EXAMPLE.com/handbook/"
+        );
+    }
+
+    #[test]
+    fn site_domain_links() {
+        let data = test_raw_parse_with_args(
+            vec![
+                "<html><body>",
+                "<a href='https://our-site.com/beets/#beets'>Beets</a>",
+                "<a href='https://www.our-site.com/carrots/'>Carrots</a>",
+                "<a href='https://other-site.com/kale/'>Kale</a>",
+                "</body></html>",
+            ],
+            &beets_globals(),
+            vec!["--site-domains", "our-site.com,https://www.our-site.com"],
+        );
+
+        assert!(data.warnings.is_empty());
+        assert_eq!(data.issues.len(), 1);
+        assert_eq!(
+            data.issues[0].to_string(),
+            "Dead Link: <a> links to https://www.our-site.com/carrots/, but that page does not exist"
+        );
+    }
+
+    #[test]
+    fn autofix_absolute_links() {
+        let data = test_raw_parse_with_args(
+            vec!["<html><body><a href='https://our-site.com/beets?q=1#beets'>Beets</a></body></html>"],
+            &beets_globals(),
+            vec![
+                "--site-domains",
+                "our-site.com",
+                "--relative-links",
+                "--autofix",
+            ],
+        );
+
+        assert!(data.issues.is_empty());
+        let fixes: Vec<_> = data.fixes.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            fixes,
+            vec![
+                "Absolute Link: Rewrote link from https://our-site.com/beets?q=1#beets to /beets?q=1#beets",
+                "Non-trailing: Rewrote link from /beets?q=1#beets to /beets/?q=1#beets",
+            ]
+        );
+        assert_eq!(
+            String::from_utf8(data.output.unwrap()).unwrap(),
+            "<html><body><a href=\"/beets/?q=1#beets\">Beets</a></body></html>"
+        );
+    }
+
+    #[test]
+    fn insecure_links() {
+        let data = test_raw_parse_with_args(
+            vec![
+                "<html><body>",
+                "<a href='http://beets.com/'>Beets</a>",
+                "<a href='HTTP://carrots.com:8080/'>Carrots</a>",
+                "<a href='http://kale.com/'>Kale</a>",
+                "<a href='https://lettuce.com/'>Lettuce</a>",
+                "<a href='//lettuce.com/'>Lettuce</a>",
+                "</body></html>",
+            ],
+            &Globals::default(),
+            vec!["--insecure-hosts", "carrots.com,http://kale.com/"],
+        );
+
+        assert!(data.warnings.is_empty());
+        let issues: Vec<_> = data.issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec!["Insecure Link: <a> links to http://beets.com/ using http instead of https"]
+        );
+    }
+
+    #[test]
+    fn autofix_insecure_links() {
+        let data = test_raw_parse_with_args(
+            vec!["<html><body><a href='http://beets.com/?q=1'>Beets</a></body></html>"],
+            &Globals::default(),
+            vec!["--autofix"],
+        );
+
+        assert!(data.issues.is_empty());
+        assert_eq!(
+            data.fixes[0].to_string(),
+            "Insecure Link: Rewrote link from http://beets.com/?q=1 to https://beets.com/?q=1"
+        );
+        assert_eq!(
+            String::from_utf8(data.output.unwrap()).unwrap(),
+            "<html><body><a href=\"https://beets.com/?q=1\">Beets</a></body></html>"
+        );
+    }
+
+    #[test]
+    fn mixed_content() {
+        let input = vec![
+            "<html><head>",
+            "<link rel='stylesheet' href='http://cdn.com/a.css'>",
+            "<link rel='icon' href='http://cdn.com/a.ico'>",
+            "<link rel='canonical' href='http://beets.com/'>",
+            "<script src='http://cdn.com/a.js'></script>",
+            "</head><body>",
+            "<iframe src='http://video.com/embed'></iframe>",
+            "<img src='http://cdn.com/a.png' srcset='https://cdn.com/b.png 2x'>",
+            "<a href='http://beets.com/'>Beets</a>",
+            "</body></html>",
+        ];
+        let data = test_raw_parse_with_args(input.clone(), &Globals::default(), vec![]);
+
+        let issues: Vec<_> = data.issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                "Active Mixed Content: <link href> loads http://cdn.com/a.css over http, which browsers will block on an https page",
+                "Active Mixed Content: <script src> loads http://cdn.com/a.js over http, which browsers will block on an https page",
+                "Active Mixed Content: <iframe src> loads http://video.com/embed over http, which browsers will block on an https page",
+                "Insecure Link: <a> links to http://beets.com/ using http instead of https",
+            ]
+        );
+        let warnings: Vec<_> = data.warnings.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "Passive Mixed Content: <link href> loads http://cdn.com/a.ico over http, which browsers will flag as insecure on an https page",
+                "Passive Mixed Content: <img src> loads http://cdn.com/a.png over http, which browsers will flag as insecure on an https page",
+            ]
+        );
+
+        // Sites served over http can't have mixed content
+        let data = test_raw_parse_with_args(
+            input,
+            &Globals::default(),
+            vec!["--base-url", "http://our-site.com/"],
+        );
+        assert!(data.warnings.is_empty());
+        assert_eq!(data.issues.len(), 1);
+    }
+
+    #[test]
+    fn autofix_mixed_content() {
+        let data = test_raw_parse_with_args(
+            vec!["<html><head><script src='http://cdn.com/a.js'></script></head></html>"],
+            &Globals::default(),
+            vec!["--autofix"],
+        );
+
+        assert!(data.issues.is_empty());
+        assert_eq!(
+            data.fixes[0].to_string(),
+            "Active Mixed Content: Rewrote link from http://cdn.com/a.js to https://cdn.com/a.js"
+        );
+    }
+
+    #[test]
+    fn issue_positions() {
+        let data = test_raw_parse(vec![
+            "<html><body>\n<p>Beets</p><a href='/carrots/'>Carrots</a>\n  <im",
+            "g\n src='/kale.png'>\n<a>Missing</a>",
+            "</body></html>",
+        ]);
+
+        let positions: Vec<_> = data
+            .issues
+            .iter()
+            .map(|i| (i.element.as_str(), i.position))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                (
+                    "a",
+                    Position {
+                        offset: 25,
+                        line: 2,
+                        column: 13
+                    }
+                ),
+                (
+                    "img",
+                    Position {
+                        offset: 59,
+                        line: 3,
+                        column: 3
+                    }
+                ),
+                (
+                    "a",
+                    Position {
+                        offset: 82,
+                        line: 5,
+                        column: 1
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn issue_positions_after_autofix() {
+        let input = vec![
+            "<html><body>\n",
+            "<a href=http://beets.com/>Beets</a><img src='http://beets.com/beets.png'>\n",
+            "<a href='/carrots/'>Carrots</a>",
+            "</body></html>",
+        ];
+        let data = test_raw_parse_with_args(input.clone(), &Globals::default(), vec!["--autofix"]);
+        let html = input.concat();
+
+        assert_eq!(data.fixes.len(), 2);
+        assert_eq!(data.fixes[1].position.offset, html.find("<img").unwrap());
+        assert_eq!(data.issues.len(), 1);
+        assert_eq!(
+            data.issues[0].position,
+            Position {
+                offset: html.find("<a href='/carrots/'>").unwrap(),
+                line: 3,
+                column: 1
+            }
+        );
+    }
+
+    #[test]
+    fn source_attributes() {
+        let data = test_raw_parse(vec![
+            "<html data-sitefix-source='layouts/base.html'><body>",
+            "<main data-sitefix-source='content/beets.md:3'><p><a href='/beets/'>Beets</a></p></main>",
+            "<a href='/carrots/'>Carrots</a>",
+            "</body></html>",
+        ]);
+
+        let sources: Vec<_> = data
+            .issues
+            .iter()
+            .map(|i| i.source.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(sources, vec!["content/beets.md:3", "layouts/base.html"]);
+        assert!(!data.stripped_sources);
+    }
+
+    #[test]
+    fn strip_source_attributes() {
+        let input = vec![
+            "<html><body>\n",
+            "<main data-sitefix-source='content/beets.md:3'><a href='http://beets.com/'>Beets</a></main>\n",
+            "<a href='/carrots/'>Carrots</a>",
+            "</body></html>",
+        ];
+        let data = test_raw_parse_with_args(
+            input.clone(),
+            &Globals::default(),
+            vec!["--autofix", "--strip-source-attributes"],
+        );
+
+        assert!(data.stripped_sources);
+        assert_eq!(
+            data.fixes[0].source,
+            Some(SourceLocation::parse("content/beets.md:3"))
+        );
+        assert_eq!(
+            data.issues[0].position.offset,
+            input.concat().find("<a href='/carrots/'>").unwrap()
+        );
+        assert_eq!(
+            String::from_utf8(data.output.unwrap()).unwrap(),
+            "<html><body>\n<main><a href=\"https://beets.com/\">Beets</a></main>\n<a href='/carrots/'>Carrots</a></body></html>"
+        );
+    }
+
+    #[test]
+    fn rules_turned_off_are_not_fixed() {
+        let data = test_raw_parse_with_args(
+            vec!["<html><body><a href='http://beets.com/'>Beets</a></body></html>"],
+            &Globals::default(),
+            vec!["--autofix", "--rules", "insecure-link=off"],
+        );
+
+        assert!(data.fixes.is_empty());
+        assert_eq!(
+            String::from_utf8(data.output.unwrap()).unwrap(),
+            "<html><body><a href='http://beets.com/'>Beets</a></body></html>"
+        );
+    }
 }
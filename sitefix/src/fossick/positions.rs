@@ -0,0 +1,232 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::Position;
+
+/// Works out where elements sit in the original document as it's fed to the parser.
+///
+/// lol_html doesn't expose source locations, but by the time an element handler runs
+/// everything before that element has been written to the output sink, so the number
+/// of bytes written so far is the element's offset in the output. That matches the input
+/// until autofix rewrites a tag, so we also keep track of how far rewrites shift the output.
+pub struct PositionTracker {
+    // Bytes of input written to the parser so far, across all chunks
+    read: usize,
+    // The offset of the first byte of each line in the input
+    line_starts: Vec<usize>,
+    // The input from the start of the line we're on, so that columns can be counted
+    // in characters, and so that rewritten tags can be measured against the original
+    input: Vec<u8>,
+    // The offset of the first byte still held in the input
+    input_start: usize,
+    // Bytes written to the output sink so far
+    written: Rc<Cell<usize>>,
+    // When autofixing we also hold onto the output, so that rewritten tags can be measured
+    output: Option<Rc<RefCell<Vec<u8>>>>,
+    // How many bytes longer the output is than the input, from the rewrites so far
+    shift: isize,
+    // The output and input offsets of a tag we've rewritten,
+    // which can't be measured until the rewriter has written it out
+    pending_rewrite: Option<(usize, usize)>,
+}
+
+impl PositionTracker {
+    pub fn new(written: Rc<Cell<usize>>, output: Option<Rc<RefCell<Vec<u8>>>>) -> Self {
+        Self {
+            read: 0,
+            line_starts: vec![0],
+            input: Vec::new(),
+            input_start: 0,
+            written,
+            output,
+            shift: 0,
+            pending_rewrite: None,
+        }
+    }
+
+    /// Records a chunk of input, before it's handed to the parser
+    pub fn read(&mut self, chunk: &[u8]) {
+        let read = self.read;
+        self.line_starts.extend(
+            chunk
+                .iter()
+                .enumerate()
+                .filter(|(_, byte)| **byte == b'\n')
+                .map(|(i, _)| read + i + 1),
+        );
+        self.input.extend_from_slice(chunk);
+        self.read += chunk.len();
+    }
+
+    /// The position of the element whose handler is currently running
+    pub fn current(&mut self) -> Position {
+        self.measure_rewrite();
+        let offset = (self.written.get() as isize - self.shift).max(0) as usize;
+        let position = self.position(offset);
+
+        // Later elements can't start before this one, so the lines before it can go
+        let line_start = self.line_starts[position.line - 1];
+        if line_start > self.input_start {
+            let trimmed = (line_start - self.input_start).min(self.input.len());
+            self.input.drain(..trimmed);
+            self.input_start += trimmed;
+        }
+        position
+    }
+
+    /// Notes that the element at this position is being rewritten,
+    /// so that the positions of later elements can account for it
+    pub fn rewrote(&mut self, position: &Position) {
+        self.pending_rewrite = Some((self.written.get(), position.offset));
+    }
+
+    fn measure_rewrite(&mut self) {
+        let (output_offset, input_offset) = match self.pending_rewrite {
+            Some((output_offset, input_offset)) if self.written.get() > output_offset => {
+                (output_offset, input_offset)
+            }
+            // Another handler for the same element, which hasn't been written out yet
+            _ => return,
+        };
+        self.pending_rewrite = None;
+        let Some(output) = &self.output else {
+            return;
+        };
+        let output = output.borrow();
+        let lengths = (
+            output.get(output_offset..).and_then(start_tag_len),
+            input_offset
+                .checked_sub(self.input_start)
+                .and_then(|start| self.input.get(start..))
+                .and_then(start_tag_len),
+        );
+        if let (Some(output_len), Some(input_len)) = lengths {
+            self.shift += output_len as isize - input_len as isize;
+        }
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let text = line_start
+            .checked_sub(self.input_start)
+            .and_then(|start| self.input.get(start..offset - self.input_start));
+        let column = match text {
+            // Counts characters, by skipping the continuation bytes of multi-byte characters
+            Some(text) => text.iter().filter(|byte| *byte & 0xC0 != 0x80).count(),
+            None => offset - line_start,
+        };
+        Position {
+            offset,
+            line,
+            column: column + 1,
+        }
+    }
+}
+
+// The length of the start tag at the beginning of the HTML, following
+// the tokenizer's rules for where quoted attribute values begin and end.
+fn start_tag_len(html: &[u8]) -> Option<usize> {
+    let mut i = 1;
+    while let Some(byte) = html.get(i) {
+        match byte {
+            b'>' => return Some(i + 1),
+            b'=' => {
+                i += 1;
+                while html.get(i).is_some_and(u8::is_ascii_whitespace) {
+                    i += 1;
+                }
+                if let Some(quote @ (b'"' | b'\'')) = html.get(i) {
+                    let close = html[i + 1..].iter().position(|byte| byte == quote)?;
+                    i += close + 2;
+                }
+                // Unquoted values end at whitespace or the end of the tag
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_tag_lengths() {
+        assert_eq!(start_tag_len(b"<a href=\"/beets/\">Beets</a>"), Some(18));
+        assert_eq!(start_tag_len(b"<a title='>' href=/beets>"), Some(25));
+        assert_eq!(start_tag_len(b"<a title = \">\"\n>"), Some(16));
+        assert_eq!(start_tag_len(b"<img src=>"), Some(10));
+        assert_eq!(start_tag_len(b"<a href=\"/beets"), None);
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let written = Rc::new(Cell::new(0));
+        let mut tracker = PositionTracker::new(Rc::clone(&written), None);
+        tracker.read("<p>Ünïcödé 🥕 <a>\n<b>".as_bytes());
+
+        written.set(20);
+        assert_eq!(
+            tracker.current(),
+            Position {
+                offset: 20,
+                line: 1,
+                column: 14
+            }
+        );
+        written.set(24);
+        assert_eq!(
+            tracker.current(),
+            Position {
+                offset: 24,
+                line: 2,
+                column: 1
+            }
+        );
+        assert_eq!(tracker.input, b"<b>");
+    }
+
+    #[test]
+    fn lines_span_chunks() {
+        let mut tracker = PositionTracker::new(Rc::new(Cell::new(0)), None);
+        tracker.read(b"<p>\n<a");
+        tracker.read(b">\n\n  <b>");
+
+        assert_eq!(
+            tracker.position(0),
+            Position {
+                offset: 0,
+                line: 1,
+                column: 1
+            }
+        );
+        assert_eq!(
+            tracker.position(4),
+            Position {
+                offset: 4,
+                line: 2,
+                column: 1
+            }
+        );
+        assert_eq!(
+            tracker.position(7),
+            Position {
+                offset: 7,
+                line: 2,
+                column: 4
+            }
+        );
+        assert_eq!(
+            tracker.position(11),
+            Position {
+                offset: 11,
+                line: 4,
+                column: 3
+            }
+        );
+    }
+}
@@ -0,0 +1,145 @@
+// Helpers for turning the URLs written on a page into paths on the site.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref ABSOLUTE_URL: Regex = Regex::new("^(?:https?:)?//([^/?#]*)(.*)$").unwrap();
+}
+
+/// A URL split into its path, query string, and hash segment
+#[derive(Debug, PartialEq)]
+pub struct UrlParts<'a> {
+    pub path: &'a str,
+    pub query: Option<&'a str>,
+    pub hash: Option<&'a str>,
+}
+
+impl<'a> UrlParts<'a> {
+    pub fn new(url: &'a str) -> Self {
+        let (rest, hash) = match url.split_once('#') {
+            Some((rest, hash)) => (rest, Some(hash)),
+            None => (url, None),
+        };
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+        Self { path, query, hash }
+    }
+
+    /// Rebuilds the URL with a different path, keeping the query string and hash segment
+    pub fn with_path(&self, path: &str) -> String {
+        let mut url = path.to_string();
+        if let Some(query) = self.query {
+            url.push('?');
+            url.push_str(query);
+        }
+        if let Some(hash) = self.hash {
+            url.push('#');
+            url.push_str(hash);
+        }
+        url
+    }
+}
+
+/// Resolves a URL path found on a page against that page's URL,
+/// handling `./`, `../` and bare filenames the same way a browser would.
+/// An empty path refers to the page itself.
+pub fn resolve_path(page_url: &str, path: &str) -> String {
+    if path.is_empty() {
+        return page_url.to_string();
+    }
+
+    let joined = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        let directory = match page_url.rfind('/') {
+            Some(i) => &page_url[..=i],
+            None => "/",
+        };
+        format!("{directory}{path}")
+    };
+
+    let mut segments: Vec<&str> = vec![];
+    for segment in joined.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    // Paths ending in a dot segment resolve to a directory
+    let trailing = matches!(joined.rsplit('/').next(), Some("" | "." | ".."));
+
+    let mut resolved = format!("/{}", segments.join("/"));
+    if trailing && !segments.is_empty() {
+        resolved.push('/');
+    }
+    resolved
+}
+
+/// Splits an absolute (or protocol-relative) http(s) URL
+/// into its host and everything that follows the host.
+pub fn split_host(url: &str) -> Option<(&str, &str)> {
+    let captures = ABSOLUTE_URL.captures(url)?;
+    Some((captures.get(1)?.as_str(), captures.get(2)?.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splitting_urls() {
+        let parts = UrlParts::new("../beets/?q=1#top");
+        assert_eq!(parts.path, "../beets/");
+        assert_eq!(parts.query, Some("q=1"));
+        assert_eq!(parts.hash, Some("top"));
+        assert_eq!(parts.with_path("/beets/"), "/beets/?q=1#top");
+
+        let parts = UrlParts::new("/beets#a?b");
+        assert_eq!(parts.path, "/beets");
+        assert_eq!(parts.query, None);
+        assert_eq!(parts.hash, Some("a?b"));
+    }
+
+    #[test]
+    fn splitting_hosts() {
+        assert_eq!(
+            split_host("https://example.com/handbook/?q#top"),
+            Some(("example.com", "/handbook/?q#top"))
+        );
+        assert_eq!(split_host("//example.com"), Some(("example.com", "")));
+        assert_eq!(split_host("/handbook/"), None);
+        assert_eq!(split_host("mailto:beets@example.com"), None);
+    }
+
+    #[test]
+    fn resolving_paths() {
+        assert_eq!(resolve_path("/docs/beets/", "/carrots/"), "/carrots/");
+        assert_eq!(
+            resolve_path("/docs/beets/", "carrots/"),
+            "/docs/beets/carrots/"
+        );
+        assert_eq!(
+            resolve_path("/docs/beets/", "../carrots/"),
+            "/docs/carrots/"
+        );
+        assert_eq!(resolve_path("/docs/beets/", "./"), "/docs/beets/");
+        assert_eq!(resolve_path("/docs/beets/", ".."), "/docs/");
+        assert_eq!(resolve_path("/docs/beets/", "../../../../"), "/");
+        assert_eq!(
+            resolve_path("/docs/beets.html", "carrots.html"),
+            "/docs/carrots.html"
+        );
+        assert_eq!(
+            resolve_path("/docs/beets.html", "./img/logo.png"),
+            "/docs/img/logo.png"
+        );
+        assert_eq!(resolve_path("/docs/beets.html", ""), "/docs/beets.html");
+        assert_eq!(resolve_path("/", "beets"), "/beets");
+    }
+}
@@ -1,16 +1,195 @@
 use std::fmt::Display;
 
+use crate::SourceLocation;
+
 #[derive(Debug, Clone)]
 pub enum SitefixIssue {
     MissingLink(String),
     DeadLink(String),
+    DeadHash(String),
+    NonTrailing(String),
+    Trailing(String),
+    BaseHref(String),
+    AbsoluteLink(String),
+    ExternalLink(String),
+    InsecureLink(String),
+    ActiveMixedContent(String),
+    PassiveMixedContent(String),
 }
 
+impl SitefixIssue {
+    /// A stable identifier for this kind of issue, for use in machine-readable reports
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SitefixIssue::MissingLink(_) => "missing-link",
+            SitefixIssue::DeadLink(_) => "dead-link",
+            SitefixIssue::DeadHash(_) => "dead-hash",
+            SitefixIssue::NonTrailing(_) => "non-trailing",
+            SitefixIssue::Trailing(_) => "trailing",
+            SitefixIssue::BaseHref(_) => "base-href",
+            SitefixIssue::AbsoluteLink(_) => "absolute-link",
+            SitefixIssue::ExternalLink(_) => "external-link",
+            SitefixIssue::InsecureLink(_) => "insecure-link",
+            SitefixIssue::ActiveMixedContent(_) => "active-mixed-content",
+            SitefixIssue::PassiveMixedContent(_) => "passive-mixed-content",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            SitefixIssue::MissingLink(msg)
+            | SitefixIssue::DeadLink(msg)
+            | SitefixIssue::DeadHash(msg)
+            | SitefixIssue::NonTrailing(msg)
+            | SitefixIssue::Trailing(msg)
+            | SitefixIssue::BaseHref(msg)
+            | SitefixIssue::AbsoluteLink(msg)
+            | SitefixIssue::ExternalLink(msg)
+            | SitefixIssue::InsecureLink(msg)
+            | SitefixIssue::ActiveMixedContent(msg)
+            | SitefixIssue::PassiveMixedContent(msg) => msg,
+        }
+    }
+}
+
+/// Describes a kind of issue, for reporters that list the rules Sitefix checks
+pub struct Rule {
+    /// Matches the `kind` of the issues this rule raises
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub static RULES: &[Rule] = &[
+    Rule {
+        id: "missing-link",
+        name: "MissingLink",
+        description: "Links should have an href attribute.",
+    },
+    Rule {
+        id: "dead-link",
+        name: "DeadLink",
+        description: "Links and resources should point to a page or file that exists on the site.",
+    },
+    Rule {
+        id: "dead-hash",
+        name: "DeadHash",
+        description: "Hash links should point to an element id that exists on the target page.",
+    },
+    Rule {
+        id: "non-trailing",
+        name: "NonTrailing",
+        description: "Links to other pages on the site should end with a trailing slash.",
+    },
+    Rule {
+        id: "trailing",
+        name: "Trailing",
+        description: "Links to other pages on the site should not end with a trailing slash.",
+    },
+    Rule {
+        id: "base-href",
+        name: "BaseHref",
+        description: "A <base> element should point within the site, and come before any other links.",
+    },
+    Rule {
+        id: "absolute-link",
+        name: "AbsoluteLink",
+        description: "Links to the site's own domains should be written as relative links.",
+    },
+    Rule {
+        id: "external-link",
+        name: "ExternalLink",
+        description: "Links to other sites should respond successfully.",
+    },
+    Rule {
+        id: "insecure-link",
+        name: "InsecureLink",
+        description: "Links should use https rather than http.",
+    },
+    Rule {
+        id: "active-mixed-content",
+        name: "ActiveMixedContent",
+        description: "Scripts, stylesheets and frames on an https page must not be loaded over http, as browsers block them.",
+    },
+    Rule {
+        id: "passive-mixed-content",
+        name: "PassiveMixedContent",
+        description: "Images and media on an https page should not be loaded over http, as browsers flag them as insecure.",
+    },
+];
+
 impl Display for SitefixIssue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             SitefixIssue::MissingLink(msg) => write!(f, "Missing Link: {msg}"),
             SitefixIssue::DeadLink(msg) => write!(f, "Dead Link: {msg}"),
+            SitefixIssue::DeadHash(msg) => write!(f, "Dead Link: {msg}"),
+            SitefixIssue::NonTrailing(msg) => write!(f, "Non-trailing: {msg}"),
+            SitefixIssue::Trailing(msg) => write!(f, "Trailing: {msg}"),
+            SitefixIssue::BaseHref(msg) => write!(f, "Base Href: {msg}"),
+            SitefixIssue::AbsoluteLink(msg) => write!(f, "Absolute Link: {msg}"),
+            SitefixIssue::ExternalLink(msg) => write!(f, "External Link: {msg}"),
+            SitefixIssue::InsecureLink(msg) => write!(f, "Insecure Link: {msg}"),
+            SitefixIssue::ActiveMixedContent(msg) => write!(f, "Active Mixed Content: {msg}"),
+            SitefixIssue::PassiveMixedContent(msg) => write!(f, "Passive Mixed Content: {msg}"),
+        }
+    }
+}
+
+/// Where an element starts in the original file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// Bytes from the start of the file
+    pub offset: usize,
+    /// 1-based
+    pub line: usize,
+    /// 1-based, counted in characters from the start of the line
+    pub column: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// An issue found on a page, along with the element that caused it
+#[derive(Debug, Clone)]
+pub struct PageIssue {
+    pub issue: SitefixIssue,
+    /// The tag name of the element, e.g. `a`
+    pub element: String,
+    /// The attribute holding the URL, e.g. `href`
+    pub attribute: String,
+    /// The URL as written on the page
+    pub url: Option<String>,
+    /// Where the element appears in the original file
+    pub position: Position,
+    /// The source file that generated the element, if the site says
+    pub source: Option<SourceLocation>,
+}
+
+impl PageIssue {
+    pub fn new(
+        issue: SitefixIssue,
+        element: &str,
+        attribute: &str,
+        url: Option<&str>,
+        position: Position,
+    ) -> Self {
+        Self {
+            issue,
+            element: element.into(),
+            attribute: attribute.into(),
+            url: url.map(Into::into),
+            position,
+            source: None,
         }
     }
 }
+
+impl Display for PageIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.issue.fmt(f)
+    }
+}
@@ -1,30 +1,57 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use fossick::Fossicker;
+use baseline::Baseline;
+use external::{ExternalCache, ExternalChecker, ReqwestClient};
+pub use external::{HttpClient, Method, RequestError};
+use fossick::{build_asset_url, FossickedData, Fossicker};
 use futures::future::join_all;
-pub use issues::SitefixIssue;
+use hashbrown::{HashMap, HashSet};
+pub use issues::{PageIssue, Position, Rule, SitefixIssue, RULES};
 pub use options::{FixOptions, SitefixInboundConfig};
+use options::{ReportFormat, RuleLevel};
+use reporters::{text_line, Report, ReportedFix, ReportedIssue, Severity};
+pub use sources::SourceLocation;
+use sources::SourceMap;
 use wax::{Glob, WalkEntry};
 
+mod baseline;
+mod external;
 mod fossick;
 #[macro_use]
 mod logging;
 mod issues;
 mod options;
+mod reporters;
+mod sources;
 
 pub struct FixState {
     pub options: FixOptions,
+    http_client: Arc<dyn HttpClient>,
 }
 
 #[derive(Debug, Default)]
 pub struct Globals {
     pub urls: Vec<String>,
     pub paths: Vec<PathBuf>,
+    /// The URL of every file in the source directory, HTML or otherwise
+    pub assets: HashSet<String>,
+    /// The element ids available on each page, keyed by page URL
+    pub ids: HashMap<String, HashSet<String>>,
 }
 
 impl FixState {
     pub fn new(options: FixOptions) -> Self {
-        Self { options }
+        let http_client = Arc::new(ReqwestClient::new(options.version));
+        Self::with_http_client(options, http_client)
+    }
+
+    /// Creates a runner that checks external links with the given client
+    pub fn with_http_client(options: FixOptions, http_client: Arc<dyn HttpClient>) -> Self {
+        Self {
+            options,
+            http_client,
+        }
     }
 
     pub async fn walk_for_files(&mut self) -> Vec<Fossicker> {
@@ -46,9 +73,224 @@ impl FixState {
         }
     }
 
+    /// Finds every file in the source directory, regardless of the glob,
+    /// so that links to images, documents, fonts etc can be resolved.
+    pub fn walk_for_assets(&self) -> HashSet<String> {
+        Glob::new("**/*")
+            .unwrap()
+            .walk(&self.options.source)
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+            .map(|e| build_asset_url(e.path(), &self.options))
+            .collect()
+    }
+
+    /// Hard links (or copies) every file that Sitefix didn't fix
+    /// into the output directory, so that it mirrors the full site.
+    /// Returns whether every file made it into the output directory.
+    fn mirror_source(&self, fixed_files: &HashSet<PathBuf>) -> bool {
+        let log = &self.options.logger;
+
+        let all_files = Glob::new("**/*").unwrap();
+        let files = all_files
+            .walk(&self.options.source)
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+            .map(WalkEntry::into_path)
+            .filter(|p| !fixed_files.contains(p));
+
+        let mut mirrored = true;
+        for file in files {
+            let result = mirror_file(&file, &self.options.output_path(&file));
+            if let Err(error) = result {
+                log.error(format!(
+                    "Failed to write {:?} to the output directory — Error:\n{error}",
+                    file
+                ));
+                mirrored = false;
+            }
+        }
+        mirrored
+    }
+
+    /// Requests every distinct external link across the site,
+    /// and records any failures against the pages that link to them.
+    async fn check_external_links(&self, pages: &mut [FossickedData]) {
+        let log = &self.options.logger;
+
+        let urls: HashSet<String> = pages
+            .iter()
+            .flat_map(|page| page.external_links.iter().map(|link| link.url.clone()))
+            .collect();
+
+        log.status("[Checking external links]");
+
+        let mut cache = match &self.options.external_cache {
+            Some(path) => ExternalCache::load(path).unwrap_or_else(|error| {
+                log.warn(format!(
+                    "Failed to read the external link cache {:?}, so every link will be checked — Error:\n{error}",
+                    path
+                ));
+                ExternalCache::default()
+            }),
+            None => ExternalCache::default(),
+        };
+        let now = external::now();
+        let max_age = self.options.external_max_age;
+
+        let mut statuses = HashMap::new();
+        let mut uncached = HashSet::new();
+        for url in urls {
+            match cache.get(&url, max_age, now) {
+                Some(status) if !self.options.refresh_external => {
+                    statuses.insert(url, status);
+                }
+                _ => {
+                    uncached.insert(url);
+                }
+            }
+        }
+
+        log.info(format!(
+            "Checking {} external link{}",
+            uncached.len(),
+            plural!(uncached.len())
+        ));
+        if !statuses.is_empty() {
+            log.v_info(format!(
+                "Using cached results for {} external link{}",
+                statuses.len(),
+                plural!(statuses.len())
+            ));
+        }
+
+        let checker = ExternalChecker::new(Arc::clone(&self.http_client), &self.options);
+        for (url, status) in checker.check(uncached).await {
+            cache.record(&url, &status, now);
+            statuses.insert(url, status);
+        }
+
+        if let Some(path) = &self.options.external_cache {
+            cache.prune(max_age, now);
+            if let Err(error) = cache.save(path) {
+                log.warn(format!(
+                    "Failed to write the external link cache {:?} — Error:\n{error}",
+                    path
+                ));
+            }
+        }
+
+        for page in pages {
+            for link in &page.external_links {
+                match statuses
+                    .get(&link.url)
+                    .and_then(|status| status.issue(link))
+                {
+                    Some((issue, false)) => page.issues.push(issue),
+                    Some((issue, true)) => page.warnings.push(issue),
+                    None => {}
+                }
+            }
+        }
+    }
+
+    /// Fills in the source file for issues on pages that don't name one themselves
+    fn apply_source_map(&self, source_map: &SourceMap, pages: &mut [FossickedData]) {
+        for page in pages {
+            let file = page
+                .file_path
+                .strip_prefix(&self.options.source)
+                .unwrap_or(&page.file_path);
+            let source = match source_map.get(file) {
+                Some(source) => source,
+                None => continue,
+            };
+            let issues = page.issues.iter_mut().chain(&mut page.warnings);
+            for issue in issues.chain(&mut page.fixes) {
+                issue.source.get_or_insert_with(|| source.clone());
+            }
+        }
+    }
+
+    /// Sets aside issues that are recorded in the baseline, writing it first if asked to,
+    /// and lists any baseline entries that have since been fixed.
+    fn apply_baseline(&self, path: &Path, report: &mut Report) {
+        let log = &self.options.logger;
+        let written;
+        // The baseline is only loaded up front when it isn't being rewritten
+        let baseline = match &self.options.known_issues {
+            Some(known_issues) => known_issues,
+            None => {
+                written = Baseline::new(report.issues_with(Severity::Error), &self.options.source);
+                if let Err(error) = written.save(path) {
+                    log.error(format!(
+                        "Failed to write the baseline {:?} — Error:\n{error}",
+                        path
+                    ));
+                    std::process::exit(1);
+                }
+                log.info(format!(
+                    "Wrote {} issue{} to the baseline {:?}",
+                    written.issue_count(),
+                    plural!(written.issue_count()),
+                    path
+                ));
+                &written
+            }
+        };
+
+        let comparison = baseline.compare(std::mem::take(&mut report.issues), &self.options.source);
+        report.issues = comparison.new;
+
+        if !comparison.known.is_empty() {
+            log.info(format!(
+                "Ignoring {} known issue{} from the baseline",
+                comparison.known.len(),
+                plural!(comparison.known.len())
+            ));
+            for issue in &comparison.known {
+                log.v_info(text_line(&issue.file, &issue.issue));
+            }
+        }
+
+        if !comparison.fixed.is_empty() {
+            let fixed: usize = comparison.fixed.iter().map(|entry| entry.count).sum();
+            log.info(format!(
+                "{} issue{} in the baseline {} been fixed:",
+                fixed,
+                plural!(fixed),
+                if fixed == 1 { "has" } else { "have" }
+            ));
+            for entry in &comparison.fixed {
+                log.info(format!("* {entry}"));
+            }
+            log.info("Run with --write-baseline to update the baseline");
+        }
+    }
+
+    /// Writes the structured report to the report file, or to stdout
+    fn write_report(&self, report: &Report) {
+        let log = &self.options.logger;
+        let rendered = report.render(self.options.format, &self.options);
+
+        match &self.options.report_file {
+            Some(report_file) => {
+                if let Err(error) = std::fs::write(report_file, rendered) {
+                    log.error(format!(
+                        "Failed to write report file {:?} — Error:\n{error}",
+                        report_file
+                    ));
+                    std::process::exit(1);
+                }
+                log.info(format!("Wrote a report to {:?}", report_file));
+            }
+            None => print!("{rendered}"),
+        }
+    }
+
     pub async fn run(&mut self) {
         let log = &self.options.logger;
-        log.status(&format!("Running Sitefix v{}", self.options.version));
+        log.status(format!("Running Sitefix v{}", self.options.version));
         log.v_info("Running in verbose mode");
 
         log.info(format!(
@@ -66,13 +308,29 @@ impl FixState {
             plural!(files.len()),
             self.options.glob
         ));
-        log.status("[Parsing files]");
+        log.status("[Indexing files]");
+
+        let files = join_all(files.into_iter().map(|f| f.index())).await;
+
+        let assets = self.walk_for_assets();
+        log.v_info(format!(
+            "Found {} file{} in total",
+            assets.len(),
+            plural!(assets.len())
+        ));
 
         let globals = Globals {
             urls: files.iter().flat_map(|f| f.urls.clone()).collect(),
             paths: files.iter().map(|f| f.file_path.clone()).collect(),
+            assets,
+            ids: files
+                .iter()
+                .flat_map(|f| f.urls.iter().map(|url| (url.clone(), f.ids.clone())))
+                .collect(),
         };
 
+        log.status("[Parsing files]");
+
         let results: Vec<_> = files
             .into_iter()
             .map(|f| f.fossick(&globals, &self.options))
@@ -104,28 +362,157 @@ impl FixState {
             plural!(all_pages.len()),
         ));
 
-        let issues: Vec<String> = all_pages
-            .into_iter()
-            .flatten()
-            .flat_map(|page| {
-                let path = page.file_path.to_str().unwrap_or("[unknown path]");
-                page.issues
-                    .into_iter()
-                    .map(|issue| format!("* {}: {}", path, issue))
-                    .collect::<Vec<_>>()
-            })
-            .collect();
+        let mut pages: Vec<FossickedData> = all_pages.into_iter().flatten().collect();
+        if self.options.check_external {
+            self.check_external_links(&mut pages).await;
+        }
+        if let Some(source_map) = &self.options.source_map {
+            self.apply_source_map(source_map, &mut pages);
+        }
+
+        let mut report = Report {
+            files: pages.iter().map(|page| page.file_path.clone()).collect(),
+            ..Report::default()
+        };
+        let mut fixed_files: HashSet<PathBuf> = HashSet::new();
+        let mut diffs: Vec<String> = vec![];
+        for page in pages {
+            let file = &page.file_path;
+            if page.rewritten {
+                fixed_files.insert(file.clone());
+            }
+            report
+                .fixes
+                .extend(page.fixes.into_iter().map(|fix| ReportedFix {
+                    file: file.clone(),
+                    fix,
+                }));
+            diffs.extend(page.diff);
+            let warnings = page
+                .warnings
+                .into_iter()
+                .map(|issue| (issue, Severity::Warning));
+            let issues = page
+                .issues
+                .into_iter()
+                .map(|issue| (issue, Severity::Error));
+            report
+                .issues
+                .extend(warnings.chain(issues).filter_map(|(issue, severity)| {
+                    // Configured rules override the severity the check gave
+                    let severity = match self.options.rule_level(issue.issue.kind()) {
+                        Some(RuleLevel::Off) => return None,
+                        Some(RuleLevel::Warn) => Severity::Warning,
+                        Some(RuleLevel::Error) => Severity::Error,
+                        None => severity,
+                    };
+                    Some(ReportedIssue {
+                        file: file.clone(),
+                        issue,
+                        severity,
+                    })
+                }));
+        }
+
+        if !report.fixes.is_empty() {
+            log.info(format!(
+                "{} {} issue{}",
+                if self.options.dry_run {
+                    "Would fix"
+                } else {
+                    "Fixed"
+                },
+                report.fixes.len(),
+                plural!(report.fixes.len())
+            ));
+
+            for fix in &report.fixes {
+                log.v_info(text_line(&fix.file, &fix.fix));
+            }
+        }
+
+        let mut mirrored = true;
+        if let Some(output) = &self.options.output {
+            if !self.options.dry_run {
+                mirrored = self.mirror_source(&fixed_files);
+                if mirrored {
+                    log.info(format!("Wrote the fixed site to {:?}", output));
+                } else {
+                    log.error(format!("The fixed site in {:?} is incomplete", output));
+                }
+            }
+        }
 
+        if self.options.dry_run {
+            let patch = diffs.concat();
+            if let Some(diff_path) = &self.options.diff {
+                if let Err(error) = std::fs::write(diff_path, patch) {
+                    log.error(format!(
+                        "Failed to write patch file {:?} — Error:\n{error}",
+                        diff_path
+                    ));
+                    std::process::exit(1);
+                }
+                log.info(format!(
+                    "Wrote a patch for {} file{} to {:?}",
+                    diffs.len(),
+                    plural!(diffs.len()),
+                    diff_path
+                ));
+            } else if !patch.is_empty() {
+                log.info(patch.trim_end());
+            }
+        }
+
+        if let Some(baseline) = &self.options.baseline {
+            self.apply_baseline(baseline, &mut report);
+        }
+
+        let warnings: Vec<_> = report.issues_with(Severity::Warning).collect();
+        if !warnings.is_empty() {
+            log.info(format!(
+                "{} warning{}:",
+                warnings.len(),
+                plural!(warnings.len())
+            ));
+
+            for warning in warnings {
+                log.info(text_line(&warning.file, &warning.issue));
+            }
+        }
+
+        let issues: Vec<_> = report.issues_with(Severity::Error).collect();
         if issues.is_empty() {
             log.info("All ok!");
         } else {
             log.error(format!("{} issue{}:", issues.len(), plural!(issues.len())));
 
-            for issue in issues {
-                log.error(issue);
+            for issue in &issues {
+                log.error(text_line(&issue.file, &issue.issue));
             }
+        }
+
+        if self.options.format != ReportFormat::Text || self.options.report_file.is_some() {
+            self.write_report(&report);
+        }
 
+        if !issues.is_empty() || !mirrored {
             std::process::exit(1);
         }
     }
 }
+
+fn mirror_file(file: &Path, destination: &Path) -> std::io::Result<()> {
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Replace any existing file rather than writing into it,
+    // as it may be a hard link from a previous run.
+    if let Err(error) = std::fs::remove_file(destination) {
+        if error.kind() != std::io::ErrorKind::NotFound {
+            return Err(error);
+        }
+    }
+
+    std::fs::hard_link(file, destination).or_else(|_| std::fs::copy(file, destination).map(|_| ()))
+}
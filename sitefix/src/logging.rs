@@ -56,6 +56,15 @@ impl Logger {
         }
     }
 
+    /// A logger that writes everything to stderr, leaving stdout free for a report
+    pub fn stderr(log_level: LogLevel) -> Self {
+        Self {
+            log_level,
+            out: Term::stderr(),
+            err: Term::stderr(),
+        }
+    }
+
     pub fn info<S: AsRef<str>>(&self, msg: S) {
         self.log(msg, LogLevel::Standard, LogStyle::Info);
     }
@@ -49,23 +49,30 @@ async fn main() {
     }
 
     config_layers.push(Layer::Env(Some("SITEFIX_".to_string())));
-    config_layers.push(Layer::Clap(matches));
+    config_layers.push(Layer::Clap(matches.clone()));
 
     match SitefixInboundConfig::with_layers(&config_layers) {
         Ok(config) => {
-            if let Ok(options) = FixOptions::load(config.clone()) {
-                let mut runner = FixState::new(options);
+            let config = config.with_repeated_args(&matches);
+            let options = match FixOptions::load(config) {
+                Ok(options) => options,
+                Err(e) => {
+                    eprintln!("Error loading Sitefix config:");
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let mut runner = FixState::new(options);
 
-                runner.run().await;
+            runner.run().await;
 
-                let duration = start.elapsed();
+            let duration = start.elapsed();
 
-                runner.options.logger.status(&format!(
-                    "Finished in {}.{} seconds",
-                    duration.as_secs(),
-                    duration.subsec_millis()
-                ));
-            }
+            runner.options.logger.status(format!(
+                "Finished in {}.{} seconds",
+                duration.as_secs(),
+                duration.subsec_millis()
+            ));
         }
         Err(e) => {
             eprintln!("Error loading Sitefix config:");
@@ -1,9 +1,21 @@
 use anyhow::{bail, Result};
-use clap::Parser;
-use std::{env, path::PathBuf};
+use clap::{ArgMatches, Parser};
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 use twelf::config;
 
+use crate::baseline::Baseline;
+use crate::fossick::urls::{split_host, UrlParts};
 use crate::logging::{LogLevel, Logger};
+use crate::sources::SourceMap;
+use crate::RULES;
 
 #[config]
 #[derive(Parser, Debug, Clone)]
@@ -30,12 +42,220 @@ pub struct SitefixInboundConfig {
     #[serde(default = "defaults::default_root_selector")]
     pub root_selector: String,
 
+    #[clap(
+        long,
+        help = "Enforce a URL style for internal links, either \"trailing\" or \"nontrailing\". Non-trailing links are only warned about by default."
+    )]
+    #[clap(required = false)]
+    #[serde(default)]
+    pub internal_urls: Option<String>,
+
+    #[clap(
+        long,
+        help = "The URL your site is deployed to, e.g. \"https://example.com/handbook/\". Pages are indexed under this path, and absolute links to this URL are checked as internal links. Sites are assumed to be served over https, and checked for mixed content, unless this is an http:// URL."
+    )]
+    #[clap(required = false)]
+    #[serde(default)]
+    pub base_url: Option<String>,
+
+    #[clap(
+        long,
+        help = "A comma-separated list of domains that serve this site, which can also be given more than once. Absolute links to these domains are checked as internal links."
+    )]
+    #[clap(required = false, value_delimiter = ',')]
+    #[serde(default)]
+    pub site_domains: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Report absolute links to the site's own domains, which should be written as relative links."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_false")]
+    pub relative_links: bool,
+
+    #[clap(
+        long,
+        help = "A comma-separated list of hosts that don't support https, which can be linked to over http. Can also be given more than once."
+    )]
+    #[clap(required = false, value_delimiter = ',')]
+    #[serde(default)]
+    pub insecure_hosts: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Check that links to other sites respond successfully. This makes network requests, so is off by default."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_false")]
+    pub check_external: bool,
+
+    #[clap(
+        long,
+        help = "The maximum number of external links to check at once. Defaults to 8."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_external_concurrency")]
+    pub external_concurrency: usize,
+
+    #[clap(
+        long,
+        help = "How many seconds to wait for an external link to respond. Defaults to 10."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_external_timeout")]
+    pub external_timeout: u64,
+
+    #[clap(
+        long,
+        help = "How many times to retry an external link that times out or returns a server error. Defaults to 2."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_external_retries")]
+    pub external_retries: u32,
+
+    #[clap(
+        long,
+        help = "The minimum number of milliseconds between requests to the same host. Defaults to 200."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_external_host_delay")]
+    pub external_host_delay: u64,
+
+    #[clap(
+        long,
+        help = "The file that external link results are cached in between runs. Defaults to \".sitefix-cache.json\". Set to an empty string to disable the cache."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_external_cache")]
+    pub external_cache: String,
+
+    #[clap(
+        long,
+        help = "How many hours a cached external link result stays valid for. Defaults to 24."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_external_max_age")]
+    pub external_max_age: u64,
+
+    #[clap(
+        long,
+        help = "Check every external link again, ignoring any cached results."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_false")]
+    pub refresh_external: bool,
+
+    #[clap(
+        long,
+        help = "Fix any issues that Sitefix knows how to resolve, rewriting the HTML files in place."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_false")]
+    pub autofix: bool,
+
+    #[clap(
+        long,
+        help = "Print a unified diff of the fixes Sitefix would make, without changing any files."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_false")]
+    pub dry_run: bool,
+
+    #[clap(
+        long,
+        help = "Write a patch file of the fixes Sitefix would make, without changing any files. Implies --dry-run."
+    )]
+    #[clap(required = false)]
+    #[serde(default)]
+    pub diff: Option<String>,
+
+    #[clap(
+        long,
+        help = "Write the fixed site to this directory instead of changing files in place. Implies --autofix. Files from earlier runs that are no longer in the site are left in place, so clear the directory first for an exact copy."
+    )]
+    #[clap(required = false)]
+    #[serde(default)]
+    pub output: Option<String>,
+
+    #[clap(
+        long,
+        help = "A JSON file mapping built files, relative to the source directory, to the source files that generated them, e.g. {\"beets/index.html\": \"content/beets.md\"}. Used for pages without data-sitefix-source attributes."
+    )]
+    #[clap(required = false)]
+    #[serde(default)]
+    pub source_map: Option<String>,
+
+    #[clap(
+        long,
+        help = "Remove data-sitefix-source attributes from pages when autofixing."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_false")]
+    pub strip_source_attributes: bool,
+
+    #[clap(
+        long,
+        help = "The format to report issues in: \"text\", \"json\", \"sarif\", \"junit\" or \"github\". Defaults to \"github\" when running in GitHub Actions, and \"text\" otherwise."
+    )]
+    #[clap(required = false)]
+    #[serde(default)]
+    pub format: Option<String>,
+
+    #[clap(long, help = "Write the report to this file instead of stdout.")]
+    #[clap(required = false)]
+    #[serde(default)]
+    pub report_file: Option<String>,
+
+    #[clap(
+        long,
+        help = "Set rules to \"off\", \"warn\" or \"error\", e.g. \"dead-link=warn,trailing=off\". Usually set as a rules table in a Sitefix configuration file. Warnings are reported without failing the run."
+    )]
+    #[clap(required = false)]
+    #[serde(default)]
+    pub rules: RuleLevels,
+
+    #[clap(
+        long,
+        help = "A baseline file of known issues, as written by --write-baseline. Issues in the baseline don't fail the run."
+    )]
+    #[clap(required = false)]
+    #[serde(default)]
+    pub baseline: Option<String>,
+
+    #[clap(
+        long,
+        help = "Record the current issues in the baseline file, so that later runs only fail on new issues."
+    )]
+    #[clap(required = false)]
+    #[serde(default = "defaults::default_false")]
+    pub write_baseline: bool,
+
     #[clap(long, short, help = "Print verbose logging while reviewing the site.")]
     #[clap(required = false)]
     #[serde(default = "defaults::default_false")]
     pub verbose: bool,
 }
 
+impl SitefixInboundConfig {
+    /// Twelf only reads the first value given for each command line argument,
+    /// so this picks up every value of the arguments that take a list.
+    pub fn with_repeated_args(mut self, matches: &ArgMatches) -> Self {
+        let values = |name| {
+            matches
+                .values_of(name)
+                .map(|values| values.map(String::from).collect())
+        };
+        if let Some(site_domains) = values("site-domains") {
+            self.site_domains = site_domains;
+        }
+        if let Some(insecure_hosts) = values("insecure-hosts") {
+            self.insecure_hosts = insecure_hosts;
+        }
+        self
+    }
+}
+
 mod defaults {
     pub fn default_glob() -> String {
         "**/*.{html}".into()
@@ -46,15 +266,161 @@ mod defaults {
     pub fn default_false() -> bool {
         false
     }
+    pub fn default_external_concurrency() -> usize {
+        8
+    }
+    pub fn default_external_timeout() -> u64 {
+        10
+    }
+    pub fn default_external_retries() -> u32 {
+        2
+    }
+    pub fn default_external_host_delay() -> u64 {
+        200
+    }
+    pub fn default_external_cache() -> String {
+        ".sitefix-cache.json".into()
+    }
+    pub fn default_external_max_age() -> u64 {
+        24
+    }
+}
+
+/// The level each rule was configured at, by rule id. Config files give a table
+/// (`[rules]` with `dead-link = "warn"`), while flags and environment variables give
+/// a comma-separated list (`dead-link=warn,trailing=off`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RuleLevelsInput")]
+pub struct RuleLevels(BTreeMap<String, String>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RuleLevelsInput {
+    Table(BTreeMap<String, String>),
+    List(String),
+}
+
+impl TryFrom<RuleLevelsInput> for RuleLevels {
+    type Error = String;
+
+    fn try_from(input: RuleLevelsInput) -> Result<Self, Self::Error> {
+        match input {
+            RuleLevelsInput::Table(rules) => Ok(Self(rules)),
+            RuleLevelsInput::List(rules) => rules.parse(),
+        }
+    }
+}
+
+impl FromStr for RuleLevels {
+    type Err = String;
+
+    fn from_str(rules: &str) -> Result<Self, Self::Err> {
+        rules
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| match rule.split_once('=') {
+                Some((id, level)) => Ok((id.trim().to_string(), level.trim().to_string())),
+                None => Err(format!("Expected rule=level, found \"{rule}\"")),
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+/// How seriously a rule's issues are taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleLevel {
+    /// Not reported, or fixed
+    Off,
+    /// Reported, but doesn't fail the run
+    Warn,
+    Error,
+}
+
+/// The trailing slash style that internal links should follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InternalUrls {
+    Trailing,
+    Nontrailing,
+}
+
+/// How the issues found should be reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Sarif,
+    Junit,
+    /// Workflow command annotations for GitHub Actions
+    Github,
+}
+
+impl ReportFormat {
+    /// Whether this format is a document that should have stdout to itself
+    pub fn is_document(&self) -> bool {
+        matches!(
+            self,
+            ReportFormat::Json | ReportFormat::Sarif | ReportFormat::Junit
+        )
+    }
 }
 
 // The configuration object used internally
 #[derive(Debug)]
 pub struct FixOptions {
     pub working_directory: PathBuf,
+    /// The repository root that reports give paths relative to. That's the
+    /// workspace in GitHub Actions, and otherwise wherever Sitefix was run from.
+    pub repo_root: PathBuf,
     pub source: PathBuf,
     pub root_selector: String,
     pub glob: String,
+    pub internal_urls: Option<InternalUrls>,
+    /// The path the site is deployed under, with no trailing slash (empty for the domain root)
+    pub base_path: String,
+    /// Hosts that the site is served from, whose absolute links are checked as internal links
+    pub site_hosts: Vec<String>,
+    /// Whether the site is served over https, which is assumed unless the base_url says otherwise
+    pub secure_site: bool,
+    /// Whether absolute links to the site's own hosts should be reported
+    pub relative_links: bool,
+    /// Hosts that can be linked to over http, lowercased and without ports
+    pub insecure_hosts: Vec<String>,
+    pub check_external: bool,
+    /// How many external links can be checked at once
+    pub external_concurrency: usize,
+    pub external_timeout: Duration,
+    pub external_retries: u32,
+    /// The minimum time between requests to the same host
+    pub external_host_delay: Duration,
+    /// Where external link results are cached, if anywhere
+    pub external_cache: Option<PathBuf>,
+    pub external_max_age: Duration,
+    /// Whether cached external link results should be ignored
+    pub refresh_external: bool,
+    /// Whether fixes should be made, either on disk or as a diff
+    pub autofix: bool,
+    pub dry_run: bool,
+    pub diff: Option<PathBuf>,
+    /// A directory to mirror the fixed site into, leaving the source untouched.
+    /// Stale files from earlier runs aren't removed.
+    pub output: Option<PathBuf>,
+    /// Maps built files to the source files that generated them
+    pub source_map: Option<SourceMap>,
+    /// Whether data-sitefix-source attributes should be removed when autofixing
+    pub strip_source_attributes: bool,
+    pub format: ReportFormat,
+    /// A file to write the report to, instead of stdout
+    pub report_file: Option<PathBuf>,
+    /// Rules that were configured away from their default level, by rule id
+    pub rules: HashMap<String, RuleLevel>,
+    /// Known issues, which are reported separately and don't fail the run
+    pub baseline: Option<PathBuf>,
+    /// The issues recorded in the baseline, unless it's about to be rewritten
+    pub known_issues: Option<Baseline>,
+    /// Whether the current issues should be recorded as the baseline
+    pub write_baseline: bool,
     pub version: &'static str,
     pub logger: Logger,
 }
@@ -66,20 +432,290 @@ impl FixOptions {
             eprintln!("Provide a --source flag, a SITEFIX_SOURCE environment variable, or a source key in a Sitefix configuration file.");
             bail!("Missing argument: source");
         } else {
+            let internal_urls = match config.internal_urls.as_deref() {
+                None => None,
+                Some("trailing") => Some(InternalUrls::Trailing),
+                Some("nontrailing") => Some(InternalUrls::Nontrailing),
+                Some(other) => {
+                    eprintln!("Unknown internal_urls style \"{other}\". Sitefix supports \"trailing\" or \"nontrailing\".");
+                    bail!("Invalid argument: internal_urls");
+                }
+            };
+
+            let (base_path, mut site_hosts) = match config.base_url.as_deref() {
+                None => (String::new(), vec![]),
+                Some(base_url) => match split_host(base_url) {
+                    Some((host, rest)) if !host.is_empty() => (
+                        normalize_base_path(UrlParts::new(rest).path),
+                        vec![host.to_lowercase()],
+                    ),
+                    None if base_url.starts_with('/') => (normalize_base_path(base_url), vec![]),
+                    _ => {
+                        eprintln!("The base_url \"{base_url}\" is not a valid http(s) URL or absolute path.");
+                        bail!("Invalid argument: base_url");
+                    }
+                },
+            };
+
+            site_hosts.extend(config.site_domains.iter().map(|domain| {
+                match split_host(domain) {
+                    Some((host, _)) => host,
+                    None => domain.trim_end_matches('/'),
+                }
+                .to_lowercase()
+            }));
+
+            if config.external_concurrency == 0 {
+                eprintln!("The external_concurrency option must be at least 1.");
+                bail!("Invalid argument: external_concurrency");
+            }
+
+            let dry_run = config.dry_run || config.diff.is_some();
+
+            let working_directory = env::current_dir().unwrap();
+            let repo_root = env::var_os("GITHUB_WORKSPACE")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| working_directory.clone());
+            let source = PathBuf::from(config.source);
+            let output = config.output.map(PathBuf::from);
+            if let Some(output) = &output {
+                let (abs_source, abs_output) = (
+                    working_directory.join(&source),
+                    working_directory.join(output),
+                );
+                if abs_output.starts_with(&abs_source) || abs_source.starts_with(&abs_output) {
+                    eprintln!(
+                        "The output directory {output:?} overlaps the source directory {source:?}."
+                    );
+                    eprintln!("Provide an output directory outside of your built site, or omit it to fix files in place.");
+                    bail!("Invalid argument: output");
+                }
+            }
+
+            let in_github_actions = env::var("GITHUB_ACTIONS").as_deref() == Ok("true");
+            let format = match config.format.as_deref() {
+                None if in_github_actions => ReportFormat::Github,
+                None | Some("text") => ReportFormat::Text,
+                Some("json") => ReportFormat::Json,
+                Some("sarif") => ReportFormat::Sarif,
+                Some("junit") => ReportFormat::Junit,
+                Some("github") => ReportFormat::Github,
+                Some(other) => {
+                    eprintln!("Unknown format \"{other}\". Sitefix supports \"text\", \"json\", \"sarif\", \"junit\" or \"github\".");
+                    bail!("Invalid argument: format");
+                }
+            };
+            let report_file = config.report_file.map(PathBuf::from);
+
+            let mut rules = HashMap::new();
+            for (id, level) in &config.rules.0 {
+                if !RULES.iter().any(|rule| rule.id == id) {
+                    let ids: Vec<_> = RULES.iter().map(|rule| rule.id).collect();
+                    eprintln!(
+                        "Unknown rule \"{id}\". Sitefix has the rules: {}.",
+                        ids.join(", ")
+                    );
+                    bail!("Invalid argument: rules");
+                }
+                let level = match level.as_str() {
+                    "off" => RuleLevel::Off,
+                    "warn" => RuleLevel::Warn,
+                    "error" => RuleLevel::Error,
+                    other => {
+                        eprintln!("Unknown level \"{other}\" for the {id} rule. Sitefix supports \"off\", \"warn\" or \"error\".");
+                        bail!("Invalid argument: rules");
+                    }
+                };
+                rules.insert(id.clone(), level);
+            }
+
+            if config.write_baseline && config.baseline.is_none() {
+                eprintln!("The write_baseline option needs a baseline file to write to.");
+                eprintln!("Provide a --baseline flag, a SITEFIX_BASELINE environment variable, or a baseline key in a Sitefix configuration file.");
+                bail!("Missing argument: baseline");
+            }
+
+            // Both files are read up front, so that a bad file fails the run before anything is fixed
+            let source_map = match config.source_map.map(PathBuf::from) {
+                None => None,
+                Some(path) => match SourceMap::load(&path) {
+                    Ok(source_map) => Some(source_map),
+                    Err(error) => {
+                        eprintln!("Failed to read the source map {path:?} — Error:\n{error}");
+                        bail!("Invalid argument: source_map");
+                    }
+                },
+            };
+            let baseline = config.baseline.map(PathBuf::from);
+            let known_issues = match &baseline {
+                Some(path) if !config.write_baseline => match Baseline::load(path) {
+                    Ok(known_issues) => Some(known_issues),
+                    Err(error) => {
+                        eprintln!("Failed to read the baseline {path:?} — Error:\n{error}");
+                        bail!("Invalid argument: baseline");
+                    }
+                },
+                _ => None,
+            };
+
             let log_level = if config.verbose {
                 LogLevel::Verbose
             } else {
                 LogLevel::Standard
             };
+            // Keep stdout clean for a structured report
+            let logger = if format.is_document() && report_file.is_none() {
+                Logger::stderr(log_level)
+            } else {
+                Logger::new(log_level)
+            };
 
             Ok(Self {
-                working_directory: env::current_dir().unwrap(),
-                source: PathBuf::from(config.source),
+                working_directory,
+                repo_root,
+                source,
                 root_selector: config.root_selector,
                 glob: config.glob,
+                internal_urls,
+                base_path,
+                site_hosts,
+                secure_site: !config
+                    .base_url
+                    .as_deref()
+                    .is_some_and(|base_url| base_url.to_ascii_lowercase().starts_with("http:")),
+                relative_links: config.relative_links,
+                insecure_hosts: config
+                    .insecure_hosts
+                    .iter()
+                    .map(|host| {
+                        let host =
+                            split_host(host).map_or(host.trim_end_matches('/'), |(host, _)| host);
+                        host.rsplit_once(':')
+                            .map_or(host, |(host, _)| host)
+                            .to_lowercase()
+                    })
+                    .collect(),
+                check_external: config.check_external,
+                external_concurrency: config.external_concurrency,
+                external_timeout: Duration::from_secs(config.external_timeout),
+                external_retries: config.external_retries,
+                external_host_delay: Duration::from_millis(config.external_host_delay),
+                external_cache: Some(config.external_cache)
+                    .filter(|path| !path.is_empty())
+                    .map(PathBuf::from),
+                // Huge ages just mean the cache never expires
+                external_max_age: Duration::from_secs(
+                    config.external_max_age.saturating_mul(60 * 60),
+                ),
+                refresh_external: config.refresh_external,
+                autofix: config.autofix || dry_run || output.is_some(),
+                dry_run,
+                diff: config.diff.map(PathBuf::from),
+                output,
+                source_map,
+                strip_source_attributes: config.strip_source_attributes,
+                format,
+                report_file,
+                rules,
+                baseline,
+                known_issues,
+                write_baseline: config.write_baseline,
                 version: env!("CARGO_PKG_VERSION"),
-                logger: Logger::new(log_level),
+                logger,
             })
         }
     }
+
+    /// The level a rule was configured at, if it was configured
+    pub fn rule_level(&self, id: &str) -> Option<RuleLevel> {
+        self.rules.get(id).copied()
+    }
+
+    /// Where the fixed version of the given source file should be written
+    pub fn output_path(&self, file_path: &Path) -> PathBuf {
+        match &self.output {
+            Some(output) => output.join(file_path.strip_prefix(&self.source).unwrap_or(file_path)),
+            None => file_path.to_path_buf(),
+        }
+    }
+}
+
+// Base paths are stored without a trailing slash, so that page URLs can be appended
+fn normalize_base_path(path: &str) -> String {
+    match path.trim_matches('/') {
+        "" => String::new(),
+        path => format!("/{path}"),
+    }
+}
+
+/// Loads the config that these command line arguments give, with a source of `public`
+#[cfg(test)]
+pub(crate) fn test_config(args: &[&str]) -> SitefixInboundConfig {
+    let matches = <SitefixInboundConfig as clap::IntoApp>::command()
+        .get_matches_from([&["sitefix", "--source", "public"], args].concat());
+    SitefixInboundConfig::with_layers(&[twelf::Layer::Clap(matches.clone())])
+        .unwrap()
+        .with_repeated_args(&matches)
+}
+
+#[cfg(test)]
+pub(crate) fn test_options(args: &[&str]) -> FixOptions {
+    FixOptions::load(test_config(args)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_site_domains() {
+        let config = test_config(&[
+            "--site-domains",
+            "beets.com",
+            "--site-domains",
+            "carrots.com,kale.com",
+        ]);
+        assert_eq!(
+            config.site_domains,
+            vec!["beets.com", "carrots.com", "kale.com"]
+        );
+
+        let config = test_config(&["--site-domains", "beets.com,carrots.com"]);
+        assert_eq!(config.site_domains, vec!["beets.com", "carrots.com"]);
+    }
+
+    #[test]
+    fn repeated_insecure_hosts() {
+        let config = test_config(&[
+            "--insecure-hosts",
+            "beets.com",
+            "--insecure-hosts",
+            "carrots.com,kale.com",
+        ]);
+        assert_eq!(
+            config.insecure_hosts,
+            vec!["beets.com", "carrots.com", "kale.com"]
+        );
+    }
+
+    #[test]
+    fn huge_external_max_age() {
+        let options = test_options(&["--external-max-age", &u64::MAX.to_string()]);
+        assert_eq!(options.external_max_age, Duration::from_secs(u64::MAX));
+    }
+
+    #[test]
+    fn rule_levels() {
+        let expected = RuleLevels(BTreeMap::from([
+            ("dead-link".to_string(), "warn".to_string()),
+            ("trailing".to_string(), "off".to_string()),
+        ]));
+
+        let table: RuleLevels =
+            serde_json::from_str(r#"{ "dead-link": "warn", "trailing": "off" }"#).unwrap();
+        assert_eq!(table, expected);
+        let list: RuleLevels = serde_json::from_str(r#""dead-link=warn, trailing=off""#).unwrap();
+        assert_eq!(list, expected);
+        assert!("dead-link".parse::<RuleLevels>().is_err());
+    }
 }
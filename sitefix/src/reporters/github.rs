@@ -0,0 +1,52 @@
+use super::{repo_path, Report, Severity};
+use crate::FixOptions;
+
+// Renders GitHub Actions workflow commands, which annotate the files in a pull request.
+// https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions
+pub fn render(report: &Report, options: &FixOptions) -> String {
+    report
+        .issues
+        .iter()
+        .map(|issue| {
+            let command = match issue.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            let file = repo_path(&issue.file, options);
+            let position = issue.issue.position;
+            let message = match &issue.issue.source {
+                Some(source) => format!("{} (from {source})", issue.issue),
+                None => issue.issue.to_string(),
+            };
+            format!(
+                "::{command} file={},line={},col={},title={}::{}\n",
+                escape_property(&file),
+                position.line,
+                position.column,
+                escape_property(&format!("Sitefix {}", issue.issue.issue.kind())),
+                escape_data(&message)
+            )
+        })
+        .collect()
+}
+
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape_data("100%\nDone"), "100%25%0ADone");
+        assert_eq!(escape_property("a:b,c"), "a%3Ab%2Cc");
+    }
+}
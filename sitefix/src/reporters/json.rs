@@ -0,0 +1,152 @@
+use serde::Serialize;
+
+use super::{display_path, Report, Severity};
+use crate::{FixOptions, PageIssue, SourceLocation};
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    version: &'a str,
+    files_checked: usize,
+    errors: usize,
+    warnings: usize,
+    issues: Vec<JsonIssue<'a>>,
+    fixes: Vec<JsonIssue<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonIssue<'a> {
+    file: &'a str,
+    kind: &'static str,
+    message: &'a str,
+    element: &'a str,
+    attribute: &'a str,
+    url: Option<&'a str>,
+    line: usize,
+    column: usize,
+    offset: usize,
+    source: Option<&'a SourceLocation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    severity: Option<Severity>,
+}
+
+impl<'a> JsonIssue<'a> {
+    fn new(file: &'a str, issue: &'a PageIssue, severity: Option<Severity>) -> Self {
+        Self {
+            file,
+            kind: issue.issue.kind(),
+            message: issue.issue.message(),
+            element: &issue.element,
+            attribute: &issue.attribute,
+            url: issue.url.as_deref(),
+            line: issue.position.line,
+            column: issue.position.column,
+            offset: issue.position.offset,
+            source: issue.source.as_ref(),
+            severity,
+        }
+    }
+}
+
+pub fn render(report: &Report, options: &FixOptions) -> String {
+    let json = JsonReport {
+        version: options.version,
+        files_checked: report.files.len(),
+        errors: report.issues_with(Severity::Error).count(),
+        warnings: report.issues_with(Severity::Warning).count(),
+        issues: report
+            .issues
+            .iter()
+            .map(|issue| {
+                JsonIssue::new(
+                    display_path(&issue.file),
+                    &issue.issue,
+                    Some(issue.severity),
+                )
+            })
+            .collect(),
+        fixes: report
+            .fixes
+            .iter()
+            .map(|fix| JsonIssue::new(display_path(&fix.file), &fix.fix, None))
+            .collect(),
+    };
+
+    serde_json::to_string_pretty(&json).expect("Report should serialize") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::test_options;
+    use crate::reporters::{ReportedFix, ReportedIssue};
+    use crate::{Position, SitefixIssue};
+    use std::path::PathBuf;
+
+    #[test]
+    fn json_report() {
+        let options = test_options(&["--format", "json"]);
+
+        let mut dead_link = PageIssue::new(
+            SitefixIssue::DeadLink("<a> links to /beets/, but that page does not exist".into()),
+            "a",
+            "href",
+            Some("/beets/"),
+            Position {
+                offset: 52,
+                line: 3,
+                column: 5,
+            },
+        );
+        dead_link.source = Some(SourceLocation::parse("content/beets.md:12"));
+        let fix = PageIssue::new(
+            SitefixIssue::NonTrailing("Rewrote link from /carrots to /carrots/".into()),
+            "a",
+            "href",
+            Some("/carrots"),
+            Position {
+                offset: 80,
+                line: 4,
+                column: 5,
+            },
+        );
+        let report = Report {
+            files: vec![
+                PathBuf::from("public/index.html"),
+                PathBuf::from("public/carrots/index.html"),
+            ],
+            issues: vec![ReportedIssue {
+                file: PathBuf::from("public/index.html"),
+                issue: dead_link,
+                severity: Severity::Error,
+            }],
+            fixes: vec![ReportedFix {
+                file: PathBuf::from("public/index.html"),
+                fix,
+            }],
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&render(&report, &options)).unwrap();
+        assert_eq!(json["files_checked"], 2);
+        assert_eq!(json["errors"], 1);
+        assert_eq!(json["warnings"], 0);
+        assert_eq!(
+            json["issues"][0],
+            serde_json::json!({
+                "file": "public/index.html",
+                "kind": "dead-link",
+                "message": "<a> links to /beets/, but that page does not exist",
+                "element": "a",
+                "attribute": "href",
+                "url": "/beets/",
+                "line": 3,
+                "column": 5,
+                "offset": 52,
+                "source": { "file": "content/beets.md", "line": 12 },
+                "severity": "error",
+            })
+        );
+        assert_eq!(json["fixes"][0]["kind"], "non-trailing");
+        assert!(json["fixes"][0].get("severity").is_none());
+        assert!(json["fixes"][0]["source"].is_null());
+    }
+}
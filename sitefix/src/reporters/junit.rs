@@ -0,0 +1,146 @@
+use std::fmt::Write;
+
+use super::{display_path, location, Report, ReportedIssue, Severity};
+
+// Renders a JUnit XML report with one testsuite per page checked.
+// Each issue on the page is a failing testcase, and pages without
+// issues get a single passing testcase so that they count towards the total.
+pub fn render(report: &Report) -> String {
+    let mut suites = String::new();
+    let mut total_tests = 0;
+    let mut total_failures = 0;
+
+    for file in &report.files {
+        let path = display_path(file);
+        let issues: Vec<&ReportedIssue> = report
+            .issues
+            .iter()
+            .filter(|issue| &issue.file == file)
+            .collect();
+        let failures = issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .count();
+
+        let mut cases = String::new();
+        for issue in &issues {
+            let kind = issue.issue.issue.kind();
+            let name = match &issue.issue.url {
+                Some(url) => format!("{kind}: {url}"),
+                None => kind.to_string(),
+            };
+            let message = format!("{}: {}", location(file, &issue.issue), issue.issue);
+            let _ = writeln!(
+                cases,
+                "    <testcase name=\"{}\" classname=\"{}\">",
+                escape(&name),
+                escape(path)
+            );
+            let _ = match issue.severity {
+                Severity::Error => writeln!(
+                    cases,
+                    "      <failure type=\"{kind}\" message=\"{}\">{}</failure>",
+                    escape(issue.issue.issue.message()),
+                    escape(&message)
+                ),
+                Severity::Warning => writeln!(
+                    cases,
+                    "      <system-out>Warning: {}</system-out>",
+                    escape(&message)
+                ),
+            };
+            cases.push_str("    </testcase>\n");
+        }
+
+        let mut tests = issues.len();
+        if failures == 0 {
+            let _ = writeln!(
+                cases,
+                "    <testcase name=\"All checks\" classname=\"{}\"/>",
+                escape(path)
+            );
+            tests += 1;
+        }
+
+        total_tests += tests;
+        total_failures += failures;
+        let _ = write!(
+            suites,
+            "  <testsuite name=\"{}\" tests=\"{tests}\" failures=\"{failures}\" errors=\"0\">\n{cases}  </testsuite>\n",
+            escape(path)
+        );
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <testsuites name=\"Sitefix\" tests=\"{total_tests}\" failures=\"{total_failures}\" errors=\"0\">\n\
+        {suites}</testsuites>\n"
+    )
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporters::ReportedIssue;
+    use crate::{PageIssue, Position, SitefixIssue};
+    use std::path::PathBuf;
+
+    #[test]
+    fn junit_report() {
+        let report = Report {
+            files: vec![
+                PathBuf::from("public/index.html"),
+                PathBuf::from("public/beets/index.html"),
+            ],
+            issues: vec![ReportedIssue {
+                file: PathBuf::from("public/index.html"),
+                issue: PageIssue::new(
+                    SitefixIssue::DeadLink(
+                        "<a> links to /carrots/, but that page does not exist".into(),
+                    ),
+                    "a",
+                    "href",
+                    Some("/carrots/"),
+                    Position {
+                        offset: 30,
+                        line: 2,
+                        column: 7,
+                    },
+                ),
+                severity: Severity::Error,
+            }],
+            fixes: vec![],
+        };
+
+        let xml = render(&report);
+        assert!(
+            xml.contains("<testsuites name=\"Sitefix\" tests=\"2\" failures=\"1\" errors=\"0\">")
+        );
+        assert!(xml.contains(
+            "<testsuite name=\"public/index.html\" tests=\"1\" failures=\"1\" errors=\"0\">"
+        ));
+        assert!(xml
+            .contains("<testcase name=\"dead-link: /carrots/\" classname=\"public/index.html\">"));
+        assert!(xml.contains(
+            "<failure type=\"dead-link\" message=\"&lt;a&gt; links to /carrots/, but that page does not exist\">public/index.html:2:7: Dead Link: "
+        ));
+        assert!(
+            xml.contains("<testcase name=\"All checks\" classname=\"public/beets/index.html\"/>")
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::{Component, Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::options::ReportFormat;
+use crate::{FixOptions, PageIssue};
+
+mod github;
+mod json;
+mod junit;
+mod sarif;
+
+/// Whether an issue fails the run, or is only reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// An issue along with the file it was found in
+#[derive(Debug)]
+pub struct ReportedIssue {
+    pub file: PathBuf,
+    pub issue: PageIssue,
+    pub severity: Severity,
+}
+
+/// A fix that was made (or would be made) to a file
+#[derive(Debug)]
+pub struct ReportedFix {
+    pub file: PathBuf,
+    pub fix: PageIssue,
+}
+
+/// Everything Sitefix found across the site, for reporters to render
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Every file that was checked, whether or not it had issues
+    pub files: Vec<PathBuf>,
+    pub issues: Vec<ReportedIssue>,
+    pub fixes: Vec<ReportedFix>,
+}
+
+impl Report {
+    pub fn issues_with(&self, severity: Severity) -> impl Iterator<Item = &ReportedIssue> {
+        self.issues
+            .iter()
+            .filter(move |issue| issue.severity == severity)
+    }
+
+    /// Renders the report in the given format
+    pub fn render(&self, format: ReportFormat, options: &FixOptions) -> String {
+        match format {
+            ReportFormat::Text => self
+                .issues
+                .iter()
+                .map(|issue| format!("{}\n", text_line(&issue.file, &issue.issue)))
+                .collect(),
+            ReportFormat::Json => json::render(self, options),
+            ReportFormat::Sarif => sarif::render(self, options),
+            ReportFormat::Junit => junit::render(self),
+            ReportFormat::Github => github::render(self, options),
+        }
+    }
+}
+
+/// How an issue or fix is listed in Sitefix's human-readable output
+pub fn text_line(file: &Path, issue: &PageIssue) -> String {
+    format!("* {}: {}", location(file, issue), issue)
+}
+
+/// Where an issue is, as `path:line:col`, along with the source file that generated it
+pub fn location(file: &Path, issue: &PageIssue) -> String {
+    let location = format!("{}:{}", display_path(file), issue.position);
+    match &issue.source {
+        Some(source) => format!("{location} (from {source})"),
+        None => location,
+    }
+}
+
+pub fn display_path(file: &Path) -> &str {
+    file.to_str().unwrap_or("[unknown path]")
+}
+
+// Code review tools expect paths relative to the repository root, but the source
+// directory is relative to wherever Sitefix was run from, or else absolute.
+pub fn repo_path(file: &Path, options: &FixOptions) -> String {
+    let absolute = options.working_directory.join(file);
+    let relative = absolute.strip_prefix(&options.repo_root).unwrap_or(file);
+
+    let relative: PathBuf = relative
+        .components()
+        .filter(|component| component != &Component::CurDir)
+        .collect();
+    display_path(&relative).replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::test_options;
+
+    #[test]
+    fn repo_paths() {
+        let mut options = test_options(&[]);
+        options.working_directory = PathBuf::from("/repo/site");
+
+        options.repo_root = PathBuf::from("/repo");
+        for file in [
+            "public/index.html",
+            "./public/index.html",
+            "/repo/site/public/index.html",
+        ] {
+            assert_eq!(
+                repo_path(Path::new(file), &options),
+                "site/public/index.html"
+            );
+        }
+
+        options.repo_root = PathBuf::from("/repo/site");
+        for file in [
+            "public/index.html",
+            "./public/index.html",
+            "/repo/site/public/index.html",
+        ] {
+            assert_eq!(repo_path(Path::new(file), &options), "public/index.html");
+        }
+
+        // Files outside of the repository keep the path they were given
+        options.repo_root = PathBuf::from("/other");
+        assert_eq!(
+            repo_path(Path::new("./public/index.html"), &options),
+            "public/index.html"
+        );
+        assert_eq!(
+            repo_path(Path::new("/elsewhere/index.html"), &options),
+            "/elsewhere/index.html"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use serde_json::{json, Value};
+
+use super::{repo_path, Report, ReportedIssue, Severity};
+use crate::{FixOptions, RULES};
+
+// Renders a SARIF 2.1.0 log, as consumed by code scanning tools.
+// https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+pub fn render(report: &Report, options: &FixOptions) -> String {
+    let rules: Vec<Value> = RULES
+        .iter()
+        .map(|rule| {
+            json!({
+                "id": rule.id,
+                "name": rule.name,
+                "shortDescription": { "text": rule.description },
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = report
+        .issues
+        .iter()
+        .map(|issue| result(issue, options))
+        .collect();
+
+    let sarif = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "Sitefix",
+                    "version": options.version,
+                    "informationUri": "https://github.com/CloudCannon/sitefix",
+                    "rules": rules,
+                }
+            },
+            "results": results,
+            "columnKind": "unicodeCodePoints",
+        }]
+    });
+
+    serde_json::to_string_pretty(&sarif).expect("Report should serialize") + "\n"
+}
+
+fn result(issue: &ReportedIssue, options: &FixOptions) -> Value {
+    let kind = issue.issue.issue.kind();
+    let level = match issue.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+
+    let mut result = json!({
+        "ruleId": kind,
+        "ruleIndex": RULES.iter().position(|rule| rule.id == kind),
+        "level": level,
+        "message": { "text": issue.issue.issue.message() },
+        "locations": [{
+            "physicalLocation": {
+                "artifactLocation": {
+                    "uri": repo_path(&issue.file, options),
+                },
+                "region": {
+                    "startLine": issue.issue.position.line,
+                    "startColumn": issue.issue.position.column,
+                    "byteOffset": issue.issue.position.offset,
+                },
+            },
+        }],
+    });
+
+    // Point back to the file the page was generated from, which is where the fix belongs
+    if let Some(source) = &issue.issue.source {
+        let mut location = json!({
+            "id": 0,
+            "message": { "text": "Source file" },
+            "physicalLocation": {
+                "artifactLocation": { "uri": source.file.replace('\\', "/") },
+            },
+        });
+        if let Some(line) = source.line {
+            location["physicalLocation"]["region"] = json!({ "startLine": line });
+        }
+        result["relatedLocations"] = json!([location]);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::test_options;
+    use crate::{PageIssue, Position, SitefixIssue, SourceLocation};
+    use std::path::PathBuf;
+
+    #[test]
+    fn sarif_report() {
+        let mut options = test_options(&["--format", "sarif"]);
+        options.working_directory = PathBuf::from("/repo/site");
+        options.repo_root = PathBuf::from("/repo");
+
+        let mut issue = PageIssue::new(
+            SitefixIssue::DeadHash(
+                "<a> links to #beets, but no such element exists on the page".into(),
+            ),
+            "a",
+            "href",
+            Some("#beets"),
+            Position {
+                offset: 30,
+                line: 2,
+                column: 7,
+            },
+        );
+        issue.source = Some(SourceLocation::parse("content/beets.md:12"));
+        let report = Report {
+            files: vec![
+                PathBuf::from("./public/index.html"),
+                PathBuf::from("/repo/site/public/carrots.html"),
+            ],
+            issues: vec![
+                ReportedIssue {
+                    file: PathBuf::from("./public/index.html"),
+                    issue: issue.clone(),
+                    severity: Severity::Warning,
+                },
+                ReportedIssue {
+                    file: PathBuf::from("/repo/site/public/carrots.html"),
+                    issue,
+                    severity: Severity::Warning,
+                },
+            ],
+            fixes: vec![],
+        };
+
+        let sarif: Value = serde_json::from_str(&render(&report, &options)).unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+
+        let run = &sarif["runs"][0];
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+        assert_eq!(rules.len(), RULES.len());
+        assert_eq!(run["columnKind"], "unicodeCodePoints");
+
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "dead-hash");
+        assert_eq!(
+            rules[result["ruleIndex"].as_u64().unwrap() as usize]["id"],
+            "dead-hash"
+        );
+        assert_eq!(result["level"], "warning");
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "site/public/index.html"
+        );
+        assert_eq!(
+            run["results"][1]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "site/public/carrots.html"
+        );
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["region"],
+            serde_json::json!({ "startLine": 2, "startColumn": 7, "byteOffset": 30 })
+        );
+        let source = &result["relatedLocations"][0]["physicalLocation"];
+        assert_eq!(source["artifactLocation"]["uri"], "content/beets.md");
+        assert_eq!(source["region"]["startLine"], 12);
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// The file (and optionally line) that a built element was generated from,
+/// e.g. `content/beets.md:12`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceLocation {
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+impl SourceLocation {
+    /// Reads a `file:line` hint, where the line is optional
+    pub fn parse(source: &str) -> Self {
+        if let Some((file, line)) = source.rsplit_once(':') {
+            if let (false, Ok(line)) = (file.is_empty(), line.parse()) {
+                return Self {
+                    file: file.into(),
+                    line: Some(line),
+                };
+            }
+        }
+        Self {
+            file: source.into(),
+            line: None,
+        }
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}", self.file),
+            None => write!(f, "{}", self.file),
+        }
+    }
+}
+
+/// A build-generated map from built files to the source files that generated them,
+/// for sites that can't add source attributes to their pages
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct SourceMap {
+    // Keyed by the built file's path within the source directory, e.g. "beets/index.html"
+    files: BTreeMap<String, String>,
+}
+
+impl SourceMap {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read(path)?;
+        serde_json::from_slice(&contents).map_err(|error| Error::new(ErrorKind::InvalidData, error))
+    }
+
+    /// The source of a built file, given its path within the source directory
+    pub fn get(&self, file: &Path) -> Option<SourceLocation> {
+        let file = file.to_str()?.replace('\\', "/");
+        self.files
+            .get(file.trim_start_matches("./"))
+            .map(|source| SourceLocation::parse(source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_locations() {
+        assert_eq!(
+            SourceLocation::parse("content/beets.md:12"),
+            SourceLocation {
+                file: "content/beets.md".into(),
+                line: Some(12)
+            }
+        );
+        assert_eq!(
+            SourceLocation::parse("content/beets.md"),
+            SourceLocation {
+                file: "content/beets.md".into(),
+                line: None
+            }
+        );
+        assert_eq!(
+            SourceLocation::parse("C:\\site\\beets.md").to_string(),
+            "C:\\site\\beets.md"
+        );
+        assert_eq!(SourceLocation::parse(":12").to_string(), ":12");
+    }
+
+    #[test]
+    fn source_map_lookup() {
+        let map: SourceMap = serde_json::from_str(
+            r#"{ "index.html": "content/_index.md", "beets/index.html": "content/beets.md:3" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            map.get(Path::new("beets/index.html")).unwrap().to_string(),
+            "content/beets.md:3"
+        );
+        assert_eq!(
+            map.get(Path::new("./index.html")).unwrap().to_string(),
+            "content/_index.md"
+        );
+        assert_eq!(map.get(Path::new("carrots/index.html")), None);
+    }
+}